@@ -0,0 +1,294 @@
+use super::predicates::{self, CharSet};
+use super::{syntax, Pos, Predicate, Regex, Syntax, Tags};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A test on the chars around a position: the one before it and the one after it, or `None` at
+/// the start or end of the input.
+pub(crate) trait Condition: Clone {
+    fn check(&mut self, prev: Option<char>, next: Option<char>) -> bool;
+
+    /// How an assertion of this condition is described by `Regex.syntax()`.
+    fn syntax(&self) -> Syntax {
+        Syntax::Opaque(syntax::type_name::<Self>())
+    }
+}
+
+impl<F: FnMut(Option<char>, Option<char>) -> bool + Clone> Condition for F {
+    fn check(&mut self, prev: Option<char>, next: Option<char>) -> bool {
+        self(prev, next)
+    }
+}
+
+/// The zero-width assertions that patterns can express, as described by `Syntax::Assert`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    /// `^`, or `\A`: the start of the input.
+    TextStart,
+    /// `$`, or `\z`: the end of the input.
+    TextEnd,
+    /// `(?m:^)`: the start of the input or of a line.
+    LineStart,
+    /// `(?m:$)`: the end of the input or of a line.
+    LineEnd,
+    /// `\b`: between a word char and a non-word char, or the start or end of the input.
+    WordBoundary,
+    /// `\B`: anywhere that isn't a word boundary.
+    NotWordBoundary,
+}
+
+/// The word chars that anchors look at, `\w`, which are only looked up once.
+pub(crate) fn word_chars() -> &'static CharSet {
+    static WORD: OnceLock<CharSet> = OnceLock::new();
+    WORD.get_or_init(predicates::word)
+}
+
+impl Anchor {
+    /// Does the condition hold between `prev` and `next`?
+    pub(crate) fn holds(&self, prev: Option<char>, next: Option<char>) -> bool {
+        let word = word_chars();
+        let is_word = |ch: Option<char>| ch.is_some_and(|ch| word.matches(ch));
+        match self {
+            Anchor::TextStart => prev.is_none(),
+            Anchor::TextEnd => next.is_none(),
+            Anchor::LineStart => prev.is_none_or(|ch| ch == '\n'),
+            Anchor::LineEnd => next.is_none_or(|ch| ch == '\n'),
+            Anchor::WordBoundary => is_word(prev) != is_word(next),
+            Anchor::NotWordBoundary => is_word(prev) == is_word(next),
+        }
+    }
+}
+
+/// Written as in patterns, like `^` or `\b`.
+impl fmt::Display for Anchor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Anchor::TextStart => "^",
+            Anchor::TextEnd => "$",
+            Anchor::LineStart => "(?m:^)",
+            Anchor::LineEnd => "(?m:$)",
+            Anchor::WordBoundary => r"\b",
+            Anchor::NotWordBoundary => r"\B",
+        })
+    }
+}
+
+impl Condition for Anchor {
+    fn check(&mut self, prev: Option<char>, next: Option<char>) -> bool {
+        self.holds(prev, next)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Assert(*self)
+    }
+}
+
+/// A zero-width assertion: matches the empty string, at positions where `condition` holds.
+///
+/// The condition is checked when the context arrives, before the strings are started, so the
+/// state is just whether it holds here, and whether the empty string is being tracked.
+#[derive(Clone)]
+pub(crate) struct Assert<C: Condition> {
+    condition: C,
+    holds: bool,
+    empty: bool,
+    tags: Option<Tags>,
+}
+
+impl<C: Condition> Assert<C> {
+    pub(crate) fn new(condition: C) -> Assert<C> {
+        Assert {
+            condition,
+            holds: false,
+            empty: false,
+            tags: None,
+        }
+    }
+}
+
+impl<C: Condition> Regex for Assert<C> {
+    fn initialize(&mut self) {
+        self.empty = false;
+        self.tags = None;
+    }
+
+    fn start(&mut self) {
+        self.empty |= self.holds;
+    }
+
+    fn advance(&mut self, _ch: char) {
+        self.empty = false;
+    }
+
+    fn accepts(&self) -> bool {
+        self.empty
+    }
+
+    fn is_dead(&self) -> bool {
+        !self.empty
+    }
+
+    fn context(&mut self, prev: Option<char>, next: Option<char>) {
+        self.holds = self.condition.check(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        true
+    }
+
+    fn syntax(&self) -> Syntax {
+        self.condition.syntax()
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, _pos: Pos) {
+        if self.holds {
+            self.empty = true;
+            self.tags = Tags::prefer(self.tags.take(), Some(tags.clone()));
+        }
+    }
+
+    fn advance_tagged(&mut self, ch: char, _pos: Pos) {
+        self.tags = None;
+        self.advance(ch);
+    }
+
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        self.tags.clone()
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.push(self.holds as u8);
+        out.push(self.empty as u8);
+        true
+    }
+}
+
+/// A condition that runs `regex` on a string of at most one char: the char before or after the
+/// position, or the empty string at the start or end of the input.
+#[derive(Clone)]
+pub(crate) struct Look<R: Regex> {
+    regex: R,
+    behind: bool,
+}
+
+impl<R: Regex> Look<R> {
+    pub(crate) fn new(regex: R, behind: bool) -> Look<R> {
+        Look { regex, behind }
+    }
+}
+
+impl<R: Regex> Condition for Look<R> {
+    fn check(&mut self, prev: Option<char>, next: Option<char>) -> bool {
+        let regex = &mut self.regex;
+        regex.initialize();
+        regex.start();
+        if let Some(ch) = if self.behind { prev } else { next } {
+            regex.advance(ch);
+        }
+        regex.accepts()
+    }
+
+    // Patterns have no lookarounds.
+    fn syntax(&self) -> Syntax {
+        Syntax::Opaque(
+            if self.behind {
+                "lookbehind"
+            } else {
+                "lookahead"
+            }
+            .to_owned(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::combinators::*;
+    use crate::{compile_dfa, Matcher, Pattern, Regex};
+
+    #[test]
+    fn assertions() {
+        let word = || plus(char_range('a', 'z'));
+        let mut whole_word = seq(word_boundary(), seq(word(), word_boundary()));
+        assert_eq!(
+            whole_word
+                .find_iter("cat, catalog; scat")
+                .collect::<Vec<_>>(),
+            [0..3, 5..12, 14..18]
+        );
+        assert!(whole_word.is_match("cat"));
+        let mut cat = seq(word_boundary(), seq(literal_ci("cat"), word_boundary()));
+        assert_eq!(
+            cat.find_iter("cat catalog Cat").collect::<Vec<_>>(),
+            [0..3, 12..15]
+        );
+
+        let mut inside = seq(not_word_boundary(), seq(achar('a'), not_word_boundary()));
+        assert_eq!(
+            inside.find_iter("a cat aab").collect::<Vec<_>>(),
+            [3..4, 7..8]
+        );
+
+        let line = || seq(line_start(), seq(plus(char_range('a', 'z')), line_end()));
+        assert_eq!(
+            line().find_iter("one\ntwo 2\nthree").collect::<Vec<_>>(),
+            [0..3, 10..15]
+        );
+        let mut whole = seq(text_start(), seq(star(dot()), text_end()));
+        assert!(whole.is_match("anything"));
+        assert_eq!(whole.find("a\nb"), Some(0..3));
+        let mut last = seq(achar('x'), text_end());
+        assert_eq!(last.find("xax x"), Some(4..5));
+        assert!(!seq(achar('a'), text_start()).is_match("a"));
+
+        // One char of lookaround.
+        let mut before_digit = seq(plus(char_range('a', 'z')), lookahead(char_range('0', '9')));
+        assert_eq!(before_digit.find("abc def9"), Some(4..7));
+        let mut after_dollar = seq(lookbehind(achar('$')), plus(char_range('0', '9')));
+        assert_eq!(after_dollar.find("12 $34"), Some(4..6));
+        assert_eq!(seq(achar('a'), lookahead(empty())).find("aa"), Some(1..2));
+
+        let mut caps = seq(group("a", word_boundary()), achar('b'));
+        let caps = caps.captures("b").unwrap();
+        assert_eq!(caps.get("a").unwrap().bytes(), 0..0);
+
+        // Patterns, streaming, and DFAs give the context too.
+        let check = |pattern: &str, input: &str| {
+            let found = Pattern::new(pattern).unwrap().find(input);
+            let expected = regex::Regex::new(pattern).unwrap().find(input);
+            assert_eq!(
+                found,
+                expected.map(|m| m.range()),
+                "{} on {:?}",
+                pattern,
+                input
+            );
+        };
+        check(r"\bis\b", "this is it");
+        check(r"\Bis", "is this");
+        check("^a+", "aaa");
+        check("^a+", "baaa");
+        check("a+$", "aab aa");
+        check(r"(?m)^b+$", "a\nbb\nc");
+        check(r"(?m:^)b", "a\nb");
+        check(r"\Aab\z", "ab");
+        check(r"x\b", "x");
+
+        let mut matcher = Matcher::new(seq(word(), word_boundary()));
+        matcher.feed("ab").unwrap();
+        assert!(matcher.is_accepting());
+        matcher.feed("c").unwrap();
+        assert!(matcher.is_accepting());
+        matcher.feed("!").unwrap();
+        assert!(!matcher.is_accepting());
+        assert_eq!(matcher.finish(), Ok(false));
+
+        let mut dfa = compile_dfa(Pattern::new(r"\w+\b.*").unwrap());
+        assert!(dfa.is_match("abc def"));
+        assert!(dfa.is_match("abc"));
+        assert_eq!(dfa.find("  ab"), Some(2..4));
+        let mut dfa = compile_dfa(Pattern::new(r"a\Bb").unwrap());
+        assert!(dfa.is_match("ab"));
+        assert!(!compile_dfa(Pattern::new(r"a\b.").unwrap()).is_match("ab"));
+    }
+}
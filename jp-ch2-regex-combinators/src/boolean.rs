@@ -0,0 +1,426 @@
+use super::{Pos, Regex, Symbol, Syntax, Tags};
+use std::collections::HashSet;
+
+// Combining `accepts()` pointwise is only right when a single string is being tracked: `left` and
+// `right` could each accept a _different_ tracked string. So these combinators keep a separate
+// copy of their parts for each tracked string, started fresh by `start`. To keep the number of
+// copies down, copies in the same state are merged, when the parts support snapshots.
+
+/// Remove each copy that is in the same state as an earlier one (which has priority for tags).
+/// Does nothing if the copies don't support snapshots.
+pub(crate) fn dedup<T>(copies: &mut Vec<T>, write_state: impl Fn(&T, &mut Vec<u8>) -> bool) {
+    if copies.len() < 2 {
+        return;
+    }
+    let mut seen = HashSet::new();
+    let mut snapshots = Vec::with_capacity(copies.len());
+    for copy in copies.iter() {
+        let mut snapshot = vec![];
+        if !write_state(copy, &mut snapshot) {
+            return;
+        }
+        snapshots.push(seen.insert(snapshot));
+    }
+    let mut is_new = snapshots.into_iter();
+    copies.retain(|_| is_new.next().unwrap());
+}
+
+/// Write the number of copies, then their snapshots in sorted order, so that the order the copies
+/// happen to be in doesn't matter.
+pub(crate) fn write_copies<T>(
+    copies: &[T],
+    write_state: impl Fn(&T, &mut Vec<u8>) -> bool,
+    out: &mut Vec<u8>,
+) -> bool {
+    let mut snapshots = vec![];
+    for copy in copies {
+        let mut snapshot = vec![];
+        if !write_state(copy, &mut snapshot) {
+            return false;
+        }
+        snapshots.push(snapshot);
+    }
+    snapshots.sort();
+    out.extend_from_slice(&(snapshots.len() as u32).to_le_bytes());
+    for snapshot in snapshots {
+        out.extend(snapshot);
+    }
+    true
+}
+
+/*************/
+/* Intersect */
+/*************/
+
+#[derive(Clone)]
+pub(crate) struct Intersect<P, Q> {
+    // Initialized copies of the parts, to start new strings with.
+    fresh: (P, Q),
+    copies: Vec<(P, Q)>,
+    // Whether the last copy was started since the last advance, so another start can be skipped.
+    started: bool,
+}
+
+impl<P, Q> Intersect<P, Q> {
+    pub(crate) fn new<T: Symbol>(mut left: P, mut right: Q) -> Intersect<P, Q>
+    where
+        P: Regex<T>,
+        Q: Regex<T>,
+    {
+        left.initialize();
+        right.initialize();
+        Intersect {
+            fresh: (left, right),
+            copies: vec![],
+            started: false,
+        }
+    }
+
+    fn write_pair<T: Symbol>(pair: &(P, Q), out: &mut Vec<u8>) -> bool
+    where
+        P: Regex<T>,
+        Q: Regex<T>,
+    {
+        pair.0.write_state(out) && pair.1.write_state(out)
+    }
+
+    fn prune<T: Symbol>(&mut self)
+    where
+        P: Regex<T>,
+        Q: Regex<T>,
+    {
+        self.copies
+            .retain(|(left, right)| !left.is_dead() && !right.is_dead());
+        dedup(&mut self.copies, Self::write_pair::<T>);
+    }
+}
+
+impl<T: Symbol, P: Regex<T>, Q: Regex<T>> Regex<T> for Intersect<P, Q> {
+    fn initialize(&mut self) {
+        self.copies.clear();
+        self.started = false;
+    }
+
+    fn start(&mut self) {
+        if !self.started {
+            let mut pair = self.fresh.clone();
+            pair.0.start();
+            pair.1.start();
+            self.copies.push(pair);
+            self.started = true;
+            self.prune::<T>();
+        }
+    }
+
+    fn advance(&mut self, ch: T) {
+        for (left, right) in &mut self.copies {
+            left.advance(ch);
+            right.advance(ch);
+        }
+        self.started = false;
+        self.prune::<T>();
+    }
+
+    fn accepts(&self) -> bool {
+        self.copies
+            .iter()
+            .any(|(left, right)| left.accepts() && right.accepts())
+    }
+
+    fn is_dead(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.copies
+            .iter()
+            .any(|(left, right)| left.is_saturated() && right.is_saturated())
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        // The fresh copies need it too, for the strings they start.
+        self.fresh.0.context(prev, next);
+        self.fresh.1.context(prev, next);
+        for (left, right) in &mut self.copies {
+            left.context(prev, next);
+            right.context(prev, next);
+        }
+    }
+
+    fn uses_context(&self) -> bool {
+        self.fresh.0.uses_context() || self.fresh.1.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Intersect(
+            Box::new(self.fresh.0.syntax()),
+            Box::new(self.fresh.1.syntax()),
+        )
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        if !self.started {
+            let mut pair = self.fresh.clone();
+            pair.0.start_tagged(tags, pos);
+            pair.1.start_tagged(tags, pos);
+            self.copies.push(pair);
+            self.started = true;
+            self.prune::<T>();
+        }
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        for (left, right) in &mut self.copies {
+            left.advance_tagged(ch, pos);
+            right.advance_tagged(ch, pos);
+        }
+        self.started = false;
+        self.prune::<T>();
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        self.copies.iter().fold(None, |best, (left, right)| {
+            let tags = match (left.accepted_tags(pos), right.accepted_tags(pos)) {
+                (Some(left), Some(right)) => Some(left.merge(&right)),
+                _ => None,
+            };
+            Tags::prefer(best, tags)
+        })
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        write_copies(&self.copies, Self::write_pair::<T>, out)
+    }
+}
+
+/**************/
+/* Complement */
+/**************/
+
+#[derive(Clone)]
+pub(crate) struct Complement<P> {
+    fresh: P,
+    // Each copy tracks one string, along with the tags it started with.
+    copies: Vec<(P, Option<Tags>)>,
+    started: bool,
+    // Whether some tracked string is accepted forever, because `regex` can never match it. If
+    // so, there's no need to keep its copy around.
+    saturated: bool,
+    saturated_tags: Option<Tags>,
+}
+
+impl<P> Complement<P> {
+    pub(crate) fn new<T: Symbol>(mut regex: P) -> Complement<P>
+    where
+        P: Regex<T>,
+    {
+        regex.initialize();
+        Complement {
+            fresh: regex,
+            copies: vec![],
+            started: false,
+            saturated: false,
+            saturated_tags: None,
+        }
+    }
+
+    fn write_copy<T: Symbol>(copy: &(P, Option<Tags>), out: &mut Vec<u8>) -> bool
+    where
+        P: Regex<T>,
+    {
+        copy.0.write_state(out)
+    }
+
+    fn prune<T: Symbol>(&mut self)
+    where
+        P: Regex<T>,
+    {
+        let mut saturated_tags = self.saturated_tags.take();
+        for (regex, tags) in &self.copies {
+            if regex.is_dead() {
+                self.saturated = true;
+                saturated_tags = Tags::prefer(saturated_tags, tags.clone());
+            }
+        }
+        self.saturated_tags = saturated_tags;
+        // Strings that `regex` is dead on are accepted forever, and strings that it is saturated
+        // on are rejected forever, so neither needs a copy.
+        self.copies
+            .retain(|(regex, _)| !regex.is_dead() && !regex.is_saturated());
+        dedup(&mut self.copies, Self::write_copy::<T>);
+    }
+
+    fn push<T: Symbol>(&mut self, regex: P, tags: Option<Tags>)
+    where
+        P: Regex<T>,
+    {
+        if !self.started {
+            self.copies.push((regex, tags));
+            self.started = true;
+            self.prune::<T>();
+        }
+    }
+}
+
+impl<T: Symbol, P: Regex<T>> Regex<T> for Complement<P> {
+    fn initialize(&mut self) {
+        self.copies.clear();
+        self.started = false;
+        self.saturated = false;
+        self.saturated_tags = None;
+    }
+
+    fn start(&mut self) {
+        let mut regex = self.fresh.clone();
+        regex.start();
+        self.push::<T>(regex, None);
+    }
+
+    fn advance(&mut self, ch: T) {
+        for (regex, _) in &mut self.copies {
+            regex.advance(ch);
+        }
+        self.started = false;
+        self.prune::<T>();
+    }
+
+    fn accepts(&self) -> bool {
+        self.saturated || self.copies.iter().any(|(regex, _)| !regex.accepts())
+    }
+
+    fn is_dead(&self) -> bool {
+        !self.saturated && self.copies.is_empty()
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.saturated
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.fresh.context(prev, next);
+        for (regex, _) in &mut self.copies {
+            regex.context(prev, next);
+        }
+    }
+
+    fn uses_context(&self) -> bool {
+        self.fresh.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Complement(Box::new(self.fresh.syntax()))
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, _pos: Pos) {
+        let mut regex = self.fresh.clone();
+        regex.start();
+        self.push::<T>(regex, Some(tags.clone()));
+    }
+
+    fn advance_tagged(&mut self, ch: T, _pos: Pos) {
+        self.advance(ch);
+    }
+
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        self.copies
+            .iter()
+            .filter(|(regex, _)| !regex.accepts())
+            .fold(self.saturated_tags.clone(), |best, (_, tags)| {
+                Tags::prefer(best, tags.clone())
+            })
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.push(self.saturated as u8);
+        write_copies(&self.copies, Self::write_copy::<T>, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::combinators::*;
+    use crate::{BoxedRegex, Regex};
+
+    fn keyword(word: &str) -> BoxedRegex {
+        let mut chars = word.chars().rev();
+        let last = boxed(achar(chars.next().unwrap()));
+        chars.fold(last, |tail, ch| boxed(seq(achar(ch), tail)))
+    }
+
+    #[test]
+    fn intersect_and_complement() {
+        let ident = seq(
+            alt(char_range('a', 'z'), achar('_')),
+            star(alt(char_range('a', 'z'), char_range('0', '9'))),
+        );
+        let keywords = alt(keyword("if"), alt(keyword("in"), keyword("for")));
+        let mut name = intersect(ident, complement(keywords));
+        assert!(name.is_match("i"));
+        assert!(name.is_match("ifs"));
+        assert!(name.is_match("fo2"));
+        assert!(!name.is_match("if"));
+        assert!(!name.is_match("for"));
+        assert!(!name.is_match("2for"));
+        assert!(!name.is_match(""));
+
+        // Both halves must accept the same string: here, one of them accepts `ab` and the other
+        // `b`, but at no point is either string accepted by both.
+        let mut never = intersect(keyword("ab"), achar('b'));
+        assert_eq!(never.find("xab"), None);
+        assert!(!seq(star(dot()), never.clone()).is_match("ab"));
+        let mut ab = intersect(seq(achar('a'), dot()), seq(dot(), achar('b')));
+        assert_eq!(ab.find("aacbab"), Some(4..6));
+
+        // Strings between `<` and `>`, with no `>` inside.
+        let mut no_close = complement(seq(star(dot()), seq(achar('>'), star(dot()))));
+        let mut tag = seq(
+            achar('<'),
+            seq(group("inner", no_close.clone()), achar('>')),
+        );
+        assert!(tag.is_match("<abc>"));
+        assert!(tag.is_match("<>"));
+        assert!(!tag.is_match("<a>b>"));
+        assert_eq!(tag.find("x<a>b>"), Some(1..4));
+        assert_eq!(
+            tag.captures("<ab>").unwrap().get("inner").unwrap().bytes(),
+            1..3
+        );
+        assert!(no_close.is_match(""));
+        assert!(!no_close.is_match("a>"));
+
+        // Cross-check against the definitions on every string of `a`s and `b`s.
+        let mut left = seq(star(achar('a')), achar('b'));
+        let mut right = seq(alt(achar('a'), achar('b')), star(achar('b')));
+        let mut both = intersect(left.clone(), right.clone());
+        let mut neither = complement(alt(left.clone(), right.clone()));
+        let mut inputs = vec![String::new()];
+        for len in 1..=6 {
+            for bits in 0..(1 << len) {
+                inputs.push(
+                    (0..len)
+                        .map(|i| if bits & (1 << i) == 0 { 'a' } else { 'b' })
+                        .collect(),
+                );
+            }
+        }
+        for input in &inputs {
+            let (l, r) = (left.is_match(input), right.is_match(input));
+            assert_eq!(both.is_match(input), l && r, "{:?}", input);
+            assert_eq!(neither.is_match(input), !l && !r, "{:?}", input);
+        }
+
+        // A complement is dead once its inner regex will accept anything, and saturated once the
+        // inner regex is dead.
+        let mut not_a = complement(achar('a'));
+        not_a.initialize();
+        not_a.start();
+        assert!(not_a.accepts() && !not_a.is_saturated());
+        not_a.advance('b');
+        assert!(not_a.is_saturated());
+        let mut a = complement(not_a.clone());
+        a.initialize();
+        a.start();
+        a.advance('b');
+        assert!(a.is_dead());
+    }
+}
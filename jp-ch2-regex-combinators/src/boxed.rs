@@ -0,0 +1,276 @@
+use super::{Pos, Regex, Symbol, Syntax, Tags};
+use std::fmt;
+
+/// An object-safe version of the `Regex` trait, so that regexes of different types can be stored
+/// behind a `dyn DynRegex`. Its `dyn_` methods mean the same as the `Regex` methods of the same
+/// name, and every `Regex` that is `Send + Sync + 'static` implements it (so regexes that hold an
+/// `Rc` or a `Cell`, say, can't be boxed). Like `Regex`, it is generic over the symbol type `T`.
+/// You'll usually want to use `BoxedRegex` rather than using this directly.
+pub trait DynRegex<T: Symbol = char> {
+    fn dyn_initialize(&mut self);
+    fn dyn_start(&mut self);
+    fn dyn_advance(&mut self, ch: T);
+    fn dyn_accepts(&self) -> bool;
+    fn dyn_is_dead(&self) -> bool;
+    fn dyn_is_saturated(&self) -> bool;
+    fn dyn_matches_every_char(&self) -> bool;
+    fn dyn_context(&mut self, prev: Option<T>, next: Option<T>);
+    fn dyn_uses_context(&self) -> bool;
+    fn dyn_syntax(&self) -> Syntax;
+    fn dyn_start_tagged(&mut self, tags: &Tags, pos: Pos);
+    fn dyn_advance_tagged(&mut self, ch: T, pos: Pos);
+    fn dyn_accepted_tags(&self, pos: Pos) -> Option<Tags>;
+    fn dyn_write_state(&self, out: &mut Vec<u8>) -> bool;
+    /// Clone this regex, state and all.
+    fn clone_box(&self) -> Box<dyn DynRegex<T> + Send + Sync>;
+}
+
+impl<T: Symbol, R: Regex<T> + Send + Sync + 'static> DynRegex<T> for R {
+    fn dyn_initialize(&mut self) {
+        <R as Regex<T>>::initialize(self);
+    }
+
+    fn dyn_start(&mut self) {
+        <R as Regex<T>>::start(self);
+    }
+
+    fn dyn_advance(&mut self, ch: T) {
+        <R as Regex<T>>::advance(self, ch);
+    }
+
+    fn dyn_accepts(&self) -> bool {
+        <R as Regex<T>>::accepts(self)
+    }
+
+    fn dyn_is_dead(&self) -> bool {
+        <R as Regex<T>>::is_dead(self)
+    }
+
+    fn dyn_is_saturated(&self) -> bool {
+        <R as Regex<T>>::is_saturated(self)
+    }
+
+    fn dyn_matches_every_char(&self) -> bool {
+        <R as Regex<T>>::matches_every_char(self)
+    }
+
+    fn dyn_context(&mut self, prev: Option<T>, next: Option<T>) {
+        <R as Regex<T>>::context(self, prev, next)
+    }
+
+    fn dyn_uses_context(&self) -> bool {
+        <R as Regex<T>>::uses_context(self)
+    }
+
+    fn dyn_syntax(&self) -> Syntax {
+        <R as Regex<T>>::syntax(self)
+    }
+
+    fn dyn_start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        <R as Regex<T>>::start_tagged(self, tags, pos);
+    }
+
+    fn dyn_advance_tagged(&mut self, ch: T, pos: Pos) {
+        <R as Regex<T>>::advance_tagged(self, ch, pos);
+    }
+
+    fn dyn_accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        <R as Regex<T>>::accepted_tags(self, pos)
+    }
+
+    fn dyn_write_state(&self, out: &mut Vec<u8>) -> bool {
+        <R as Regex<T>>::write_state(self, out)
+    }
+
+    fn clone_box(&self) -> Box<dyn DynRegex<T> + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+/// A regex of any type, behind a pointer. Unlike the `impl Regex`s returned by the combinators,
+/// all `BoxedRegex`s have the same type, so they can be stored together in a `Vec`, or chosen
+/// between at runtime. A `BoxedRegex` is itself a `Regex`, so it can be freely mixed with the
+/// other combinators. A `BoxedRegex<T>` boxes a `Regex<T>`, for any symbol type `T`.
+pub struct BoxedRegex<T: Symbol = char>(Box<dyn DynRegex<T> + Send + Sync>);
+
+impl<T: Symbol> BoxedRegex<T> {
+    pub fn new(regex: impl Regex<T> + Send + Sync + 'static) -> BoxedRegex<T> {
+        BoxedRegex(Box::new(regex))
+    }
+
+    /// Box a regex that implements `DynRegex` but not `Regex` (e.g. because it can't implement
+    /// `Clone`, but can implement `clone_box`).
+    pub fn from_dyn(regex: Box<dyn DynRegex<T> + Send + Sync>) -> BoxedRegex<T> {
+        BoxedRegex(regex)
+    }
+}
+
+impl<T: Symbol> Clone for BoxedRegex<T> {
+    fn clone(&self) -> BoxedRegex<T> {
+        BoxedRegex(self.0.clone_box())
+    }
+}
+
+impl<T: Symbol> fmt::Display for BoxedRegex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.syntax(), f)
+    }
+}
+
+impl<T: Symbol> fmt::Debug for BoxedRegex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.syntax(), f)
+    }
+}
+
+impl<T: Symbol> Regex<T> for BoxedRegex<T> {
+    fn initialize(&mut self) {
+        self.0.dyn_initialize();
+    }
+
+    fn start(&mut self) {
+        self.0.dyn_start();
+    }
+
+    fn advance(&mut self, ch: T) {
+        self.0.dyn_advance(ch);
+    }
+
+    fn accepts(&self) -> bool {
+        self.0.dyn_accepts()
+    }
+
+    fn is_dead(&self) -> bool {
+        self.0.dyn_is_dead()
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.0.dyn_is_saturated()
+    }
+
+    fn matches_every_char(&self) -> bool {
+        self.0.dyn_matches_every_char()
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.0.dyn_context(prev, next)
+    }
+
+    fn uses_context(&self) -> bool {
+        self.0.dyn_uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        self.0.dyn_syntax()
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.0.dyn_start_tagged(tags, pos);
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        self.0.dyn_advance_tagged(ch, pos);
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        self.0.dyn_accepted_tags(pos)
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        self.0.dyn_write_state(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::combinators::*;
+    use crate::{bytes, BoxedRegex, Pattern, Regex};
+
+    // A user-defined combinator: matches strings of exactly `n` chars.
+    #[derive(Clone)]
+    struct Length {
+        n: usize,
+        lengths: Vec<usize>,
+    }
+
+    impl Regex for Length {
+        fn initialize(&mut self) {
+            self.lengths.clear();
+        }
+
+        fn start(&mut self) {
+            if !self.lengths.contains(&0) {
+                self.lengths.push(0);
+            }
+        }
+
+        fn advance(&mut self, _ch: char) {
+            let n = self.n;
+            self.lengths.retain(|len| *len < n);
+            for len in &mut self.lengths {
+                *len += 1;
+            }
+        }
+
+        fn accepts(&self) -> bool {
+            self.lengths.contains(&self.n)
+        }
+
+        fn is_dead(&self) -> bool {
+            self.lengths.is_empty()
+        }
+    }
+
+    #[test]
+    fn boxed_regexes() {
+        let digits = |exact: bool| {
+            let digit = char_range('0', '9');
+            if exact {
+                boxed(seq(digit.clone(), seq(digit.clone(), digit)))
+            } else {
+                boxed(seq(digit.clone(), star(digit)))
+            }
+        };
+
+        let mut regexes: Vec<BoxedRegex> = vec![
+            digits(true),
+            digits(false),
+            boxed(Pattern::new("[a-f]+").unwrap()),
+            boxed(seq(
+                achar('#'),
+                Length {
+                    n: 2,
+                    lengths: vec![],
+                },
+            )),
+        ];
+        let matches = |regexes: &mut Vec<BoxedRegex>, input| {
+            regexes
+                .iter_mut()
+                .map(|regex| regex.is_match(input))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(matches(&mut regexes, "123"), [true, true, false, false]);
+        assert_eq!(matches(&mut regexes, "1234"), [false, true, false, false]);
+        assert_eq!(matches(&mut regexes, "cafe"), [false, false, true, false]);
+        assert_eq!(matches(&mut regexes, "#xy"), [false, false, false, true]);
+
+        // Boxed regexes mix with the other combinators, and keep their groups.
+        let mut hex = seq(
+            achar('#'),
+            group("value", alt(regexes[0].clone(), regexes[2].clone())),
+        );
+        assert!(hex.is_match("#123"));
+        assert!(hex.is_match("#beef"));
+        assert!(!hex.is_match("#12ab"));
+        let caps = hex.captures("#ace").unwrap();
+        assert_eq!(caps.get("value").unwrap().bytes(), 1..4);
+
+        // Regexes over other symbols can be boxed too.
+        let mut packets: Vec<BoxedRegex<u8>> =
+            vec![boxed(bytes::byte(0)), boxed(plus(bytes::byte_range(1, 9)))];
+        assert!(packets[0].is_match_symbols([0]));
+        assert!(packets[1].is_match_symbols([1, 9]));
+        assert!(!packets[1].is_match_symbols([0]));
+    }
+}
@@ -0,0 +1,239 @@
+//! Regexes over bytes rather than chars, for matching binary data, or text in encodings other than
+//! UTF-8 (like latin-1). These are just `Regex<u8>`s (see "Symbols" on `Regex`), so the core
+//! combinators (`seq`, `alt`, `star`, `one_of`, etc.) build them, and `is_match_symbols` matches
+//! them. This module adds the byte-specific parts: byte sets, and `utf8(regex)` to match UTF-8
+//! encoded text with a char `Regex` inside a byte regex.
+
+use super::combinators::one_of;
+use super::predicates::{any, is, range};
+use super::{Predicate, Regex};
+
+/*************/
+/* Byte Sets */
+/*************/
+
+/// A set of bytes, stored as a bitmap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteSet {
+    bits: [u64; 4],
+}
+
+impl ByteSet {
+    /// The set of bytes in any of the given (inclusive) ranges. Ranges whose start is after their
+    /// end are empty.
+    pub fn new(ranges: impl IntoIterator<Item = (u8, u8)>) -> ByteSet {
+        let mut set = ByteSet { bits: [0; 4] };
+        for (min, max) in ranges {
+            for byte in min..=max {
+                set.bits[byte as usize / 64] |= 1 << (byte % 64);
+            }
+        }
+        set
+    }
+
+    /// The set of all bytes that are not in this set.
+    pub fn negate(&self) -> ByteSet {
+        ByteSet {
+            bits: [!self.bits[0], !self.bits[1], !self.bits[2], !self.bits[3]],
+        }
+    }
+
+    /// The set of bytes in either this set or `other`.
+    pub fn union(&self, other: &ByteSet) -> ByteSet {
+        let mut bits = self.bits;
+        for (bits, other) in bits.iter_mut().zip(&other.bits) {
+            *bits |= other;
+        }
+        ByteSet { bits }
+    }
+}
+
+impl Predicate<u8> for ByteSet {
+    fn matches(&self, byte: u8) -> bool {
+        self.bits[byte as usize / 64] & (1 << (byte % 64)) != 0
+    }
+
+    fn is_universal(&self) -> bool {
+        self.bits == [!0; 4]
+    }
+}
+
+/// Match a byte in any of the given (inclusive) ranges.
+pub fn set(ranges: &[(u8, u8)]) -> ByteSet {
+    ByteSet::new(ranges.iter().copied())
+}
+
+/// Match a byte in none of the given (inclusive) ranges.
+pub fn none_of(ranges: &[(u8, u8)]) -> ByteSet {
+    set(ranges).negate()
+}
+
+/***************/
+/* Combinators */
+/***************/
+
+/// Match any single byte.
+pub fn any_byte() -> impl Regex<u8> {
+    one_of(any())
+}
+
+/// Match a single, specific, byte.
+pub fn byte(byte: u8) -> impl Regex<u8> {
+    one_of(is(byte))
+}
+
+/// Match a byte in the given range, inclusive on both ends.
+pub fn byte_range(min: u8, max: u8) -> impl Regex<u8> {
+    one_of(range(min, max))
+}
+
+/// Match UTF-8 encoded text that the char regex `regex` matches. Invalid UTF-8 never matches.
+/// The context (see `Regex.context`) isn't passed on, so assertions like `word_boundary()` inside
+/// `regex` never match.
+pub fn utf8(regex: impl Regex) -> impl Regex<u8> {
+    Utf8 {
+        aligned: regex.clone(),
+        pending: regex,
+        partial: [0; 4],
+        partial_len: 0,
+    }
+}
+
+/*********/
+/* UTF-8 */
+/*********/
+
+/// A char regex, run on UTF-8 encoded bytes.
+///
+/// Since UTF-8 is self-synchronizing, the tracked strings that are still valid UTF-8 are either
+/// all at a char boundary, or all part way through the same char, except for a string that has
+/// just been started part way through a char (which dies on the next byte, unless the others do).
+/// So it's enough to keep two copies of the regex: `aligned` for the strings at a char boundary,
+/// and `pending` for the strings part way through the char whose bytes so far are `partial`.
+#[derive(Clone)]
+struct Utf8<R: Regex> {
+    aligned: R,
+    pending: R,
+    partial: [u8; 4],
+    partial_len: usize,
+}
+
+impl<R: Regex> Regex<u8> for Utf8<R> {
+    fn initialize(&mut self) {
+        self.aligned.initialize();
+        self.pending.initialize();
+        self.partial_len = 0;
+    }
+
+    fn start(&mut self) {
+        self.aligned.start();
+    }
+
+    fn advance(&mut self, byte: u8) {
+        let is_continuation = byte & 0b1100_0000 == 0b1000_0000;
+        if byte.is_ascii() {
+            self.aligned.advance(byte as char);
+            self.pending.initialize();
+            self.partial_len = 0;
+        } else if !is_continuation {
+            // A lead byte: the aligned strings are now part way through a char, and the
+            // strings that were already part way through one are invalid.
+            std::mem::swap(&mut self.aligned, &mut self.pending);
+            self.aligned.initialize();
+            self.partial[0] = byte;
+            self.partial_len = 1;
+        } else if self.partial_len == 0 {
+            self.aligned.initialize();
+        } else {
+            self.aligned.initialize();
+            self.partial[self.partial_len] = byte;
+            self.partial_len += 1;
+            match std::str::from_utf8(&self.partial[..self.partial_len]) {
+                Ok(ch) => {
+                    self.pending.advance(ch.chars().next().unwrap());
+                    std::mem::swap(&mut self.aligned, &mut self.pending);
+                    self.partial_len = 0;
+                }
+                Err(err) if err.error_len().is_none() && self.partial_len < 4 => (),
+                Err(_) => {
+                    self.pending.initialize();
+                    self.partial_len = 0;
+                }
+            }
+        }
+    }
+
+    fn accepts(&self) -> bool {
+        self.aligned.accepts()
+    }
+
+    fn is_dead(&self) -> bool {
+        self.aligned.is_dead() && (self.partial_len == 0 || self.pending.is_dead())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::{achar, char_range, exactly, maybe, plus, seq, star};
+
+    fn is_match(regex: &mut impl Regex<u8>, input: &[u8]) -> bool {
+        regex.is_match_symbols(input.iter().copied())
+    }
+
+    #[test]
+    fn byte_regexes() {
+        // A toy binary record: a magic number, a length byte below 0x80, then any payload.
+        let mut record = seq(
+            seq(byte(0x89), seq(byte(b'R'), byte(b'C'))),
+            seq(byte_range(0x00, 0x7F), star(any_byte())),
+        );
+        assert!(is_match(&mut record, b"\x89RC\x03\xFF\x00\xFE"));
+        assert!(is_match(&mut record, b"\x89RC\x00"));
+        assert!(!is_match(&mut record, b"\x89RC\x80"));
+        assert!(!is_match(&mut record, b"RC\x00"));
+
+        // A latin-1 word: letters, including the accented ones from 0xC0 to 0xFF.
+        let latin1 = set(&[
+            (b'a', b'z'),
+            (b'A', b'Z'),
+            (0xC0, 0xD6),
+            (0xD8, 0xF6),
+            (0xF8, 0xFF),
+        ]);
+        let mut word = plus(one_of(latin1));
+        assert!(is_match(&mut word, b"caf\xE9"));
+        assert!(!is_match(&mut word, b"caf\xE9!"));
+        assert!(!is_match(&mut word, b""));
+
+        let mut hex = exactly(one_of(|b: u8| b.is_ascii_hexdigit()), 4);
+        assert!(is_match(&mut hex, b"BEEF"));
+        assert!(!is_match(&mut hex, b"BEE"));
+        assert!(!is_match(&mut hex, b"BEEFF"));
+        assert!(is_match(&mut exactly(any_byte(), 0), b""));
+
+        let mut not_nul = star(one_of(none_of(&[(0, 0)])));
+        assert!(is_match(&mut not_nul, b"abc\xFF"));
+        assert!(!is_match(&mut not_nul, b"ab\x00c"));
+        assert!(is_match(&mut maybe(byte(0)), b""));
+    }
+
+    #[test]
+    fn utf8_in_bytes() {
+        // A NUL-terminated UTF-8 string of Greek letters, after a length byte.
+        let greek = plus(char_range('α', 'ω'));
+        let mut field = seq(any_byte(), seq(utf8(greek), byte(0)));
+        assert!(is_match(&mut field, "\u{3}αβγ\0".as_bytes()));
+        assert!(!is_match(&mut field, "αβγ\0".as_bytes()));
+        assert!(!is_match(&mut field, "\u{3}αbγ\0".as_bytes()));
+        // Truncated, invalid, and overlong UTF-8.
+        assert!(!is_match(&mut field, b"\x03\xCE\xB1\xCE\x00"));
+        assert!(!is_match(&mut field, b"\x03\xCE\xB1\xB1\x00"));
+        assert!(!is_match(&mut field, b"\x03\xE0\x80\x80\x00"));
+
+        // The length byte can itself be the start of a UTF-8 sequence.
+        let mut field = seq(any_byte(), utf8(achar('€')));
+        assert!(is_match(&mut field, b"\xE2\xE2\x82\xAC"));
+        assert!(!is_match(&mut field, b"\xE2\x82\xAC"));
+    }
+}
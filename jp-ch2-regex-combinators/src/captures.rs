@@ -0,0 +1,455 @@
+use super::{Regex, Symbol, Syntax};
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::sync::Arc;
+
+/// A position in the input, counted both in bytes and in chars.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pos {
+    pub byte: usize,
+    pub char: usize,
+}
+
+impl Pos {
+    /// The position just after `ch`, if `ch` starts at this position.
+    pub(crate) fn after(self, ch: char) -> Pos {
+        Pos {
+            byte: self.byte + ch.len_utf8(),
+            char: self.char + 1,
+        }
+    }
+}
+
+/// A stretch of the input, from `start` (inclusive) to `end` (exclusive).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Pos,
+    pub end: Pos,
+}
+
+impl Span {
+    /// The byte range of this span, for slicing the input.
+    pub fn bytes(&self) -> Range<usize> {
+        self.start.byte..self.end.byte
+    }
+
+    /// The char range of this span.
+    pub fn chars(&self) -> Range<usize> {
+        self.start.char..self.end.char
+    }
+}
+
+/********/
+/* Tags */
+/********/
+
+/// The tags carried by a tracked string: the position it started at, and the positions of the
+/// groups it has passed through. Cloning is cheap; tags are only copied when a group writes to
+/// them, or when a leftmost-first search has them record a choice (see `choose`).
+#[derive(Clone, Debug, Default)]
+pub struct Tags(Arc<TagData>);
+
+#[derive(Clone, Debug, Default)]
+struct TagData {
+    origin: Pos,
+    // (name, start, end), in the order the groups were first entered. A group that has been
+    // entered but not yet left has no end.
+    groups: Vec<(Arc<str>, Pos, Option<Pos>)>,
+    // The last choice the string made, if choices are recorded.
+    choices: Option<Arc<Choice>>,
+}
+
+// The choices a string made, as a list from the last one back to the first, so that strings that
+// went the same way until some point share the choices before it.
+#[derive(Debug)]
+struct Choice {
+    branch: u8,
+    // The number of choices up to this one, including an empty one at the start.
+    depth: usize,
+    previous: Option<Arc<Choice>>,
+}
+
+impl Tags {
+    /// Tags for a string starting at `origin`, that has not passed through any groups.
+    pub fn new(origin: Pos) -> Tags {
+        Tags(Arc::new(TagData {
+            origin,
+            groups: vec![],
+            choices: None,
+        }))
+    }
+
+    /// Like `new`, but recording the choices the string makes, for `MatchKind::LeftmostFirst`.
+    pub(crate) fn ranked(origin: Pos) -> Tags {
+        let start = Choice {
+            branch: 0,
+            depth: 0,
+            previous: None,
+        };
+        Tags(Arc::new(TagData {
+            origin,
+            groups: vec![],
+            choices: Some(Arc::new(start)),
+        }))
+    }
+
+    /// The position that the tracked string started at.
+    pub fn origin(&self) -> Pos {
+        self.0.origin
+    }
+
+    /// These tags, for a string that took way `branch` out of a point where it could go several
+    /// ways, numbered in the order a backtracking matcher would try them: the left side of an
+    /// `alt` is 0 and the right side 1, and going round a `star` again is 0 and leaving it is 1.
+    /// Leftmost-first searches prefer the string whose first differing choice is lower; other
+    /// searches don't record choices, and just get the same tags back.
+    pub fn choose(&self, branch: u8) -> Tags {
+        match &self.0.choices {
+            None => self.clone(),
+            Some(previous) => {
+                let mut data = (*self.0).clone();
+                data.choices = Some(Arc::new(Choice {
+                    branch,
+                    depth: previous.depth + 1,
+                    previous: Some(previous.clone()),
+                }));
+                Tags(Arc::new(data))
+            }
+        }
+    }
+
+    /// When two tracked strings meet in the same state, only one of their tags can be kept. The
+    /// string that started earliest wins; on a tie, the one whose choices come first (see
+    /// `choose`), or if choices aren't recorded, `first`. Combinators should pass their options
+    /// in priority order: left before right in an `alt`, and strings already being tracked before
+    /// newly started ones.
+    pub fn prefer(first: Option<Tags>, second: Option<Tags>) -> Option<Tags> {
+        match (first, second) {
+            (Some(first), Some(second)) if second.precedes(&first) => Some(second),
+            (Some(first), _) => Some(first),
+            (None, second) => second,
+        }
+    }
+
+    /// Is this string preferred to `other`, by `prefer`?
+    pub(crate) fn precedes(&self, other: &Tags) -> bool {
+        let by_choices = match (&self.0.choices, &other.0.choices) {
+            (Some(a), Some(b)) => compare_choices(a, b),
+            _ => Ordering::Equal,
+        };
+        self.origin().cmp(&other.origin()).then(by_choices) == Ordering::Less
+    }
+
+    /// Add the groups from `other` (tags for the same string) that aren't in these tags.
+    pub(crate) fn merge(&self, other: &Tags) -> Tags {
+        let mut data = (*self.0).clone();
+        for group in &other.0.groups {
+            if !data.groups.iter().any(|(name, _, _)| *name == group.0) {
+                data.groups.push(group.clone());
+            }
+        }
+        Tags(Arc::new(data))
+    }
+
+    fn open(&self, name: &Arc<str>, pos: Pos) -> Tags {
+        let mut data = (*self.0).clone();
+        match data.groups.iter_mut().find(|(n, _, _)| n == name) {
+            Some(group) => *group = (name.clone(), pos, None),
+            None => data.groups.push((name.clone(), pos, None)),
+        }
+        Tags(Arc::new(data))
+    }
+
+    fn close(&self, name: &Arc<str>, pos: Pos) -> Tags {
+        let mut data = (*self.0).clone();
+        if let Some(group) = data.groups.iter_mut().find(|(n, _, _)| n == name) {
+            group.2 = Some(pos);
+        }
+        Tags(Arc::new(data))
+    }
+}
+
+/// Compare lists of choices by their first difference, or if there isn't one, by length.
+fn compare_choices(mut a: &Choice, mut b: &Choice) -> Ordering {
+    let by_depth = a.depth.cmp(&b.depth);
+    while a.depth > b.depth {
+        a = a.previous.as_deref().unwrap();
+    }
+    while b.depth > a.depth {
+        b = b.previous.as_deref().unwrap();
+    }
+    // Walk back to where the lists join, keeping the earliest difference.
+    let mut by_branch = Ordering::Equal;
+    while !std::ptr::eq(a, b) {
+        if a.branch != b.branch {
+            by_branch = a.branch.cmp(&b.branch);
+        }
+        match (a.previous.as_deref(), b.previous.as_deref()) {
+            (Some(previous_a), Some(previous_b)) => {
+                a = previous_a;
+                b = previous_b;
+            }
+            _ => break,
+        }
+    }
+    by_branch.then(by_depth)
+}
+
+/************/
+/* Captures */
+/************/
+
+/// Where a regex matched, and where each of its groups matched, as reported by
+/// `Regex.captures(&str)`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Captures {
+    span: Span,
+    groups: Vec<(Arc<str>, Span)>,
+}
+
+impl Captures {
+    pub(crate) fn new(tags: &Tags, end: Pos) -> Captures {
+        let groups = tags
+            .0
+            .groups
+            .iter()
+            .filter_map(|(name, start, end)| {
+                end.map(|end| (name.clone(), Span { start: *start, end }))
+            })
+            .collect();
+        Captures {
+            span: Span {
+                start: tags.origin(),
+                end,
+            },
+            groups,
+        }
+    }
+
+    /// The span of the whole match.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// The span of the group called `name`, or `None` if it did not take part in the match.
+    pub fn get(&self, name: &str) -> Option<Span> {
+        self.groups
+            .iter()
+            .find(|(n, _)| &**n == name)
+            .map(|(_, span)| *span)
+    }
+
+    /// The name and span of each group that took part in the match, in the order they were
+    /// entered.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Span)> {
+        self.groups.iter().map(|(name, span)| (&**name, *span))
+    }
+
+    /// Append `replacement` to `dst`, with each `$name` or `${name}` in it replaced by the text
+    /// of `input` that the group `name` matched (or nothing, if it didn't take part in the
+    /// match), as in the `regex` crate. `$0` is the whole match, and `$$` is a literal `$`. A bare
+    /// name is as long as possible, so write `${1}x` rather than `$1x`.
+    pub fn expand(&self, input: &str, mut replacement: &str, dst: &mut String) {
+        while let Some(i) = replacement.find('$') {
+            dst.push_str(&replacement[..i]);
+            replacement = &replacement[i + 1..];
+            if let Some(rest) = replacement.strip_prefix('$') {
+                dst.push('$');
+                replacement = rest;
+                continue;
+            }
+            let (name, rest) = match group_ref(replacement) {
+                Some(group_ref) => group_ref,
+                None => {
+                    dst.push('$');
+                    continue;
+                }
+            };
+            replacement = rest;
+            // Numbers refer to the groups named after their positions, so `$01` is `$1`.
+            let span = match name.parse::<u32>() {
+                Ok(0) => Some(self.span),
+                Ok(n) => self.get(&n.to_string()),
+                Err(_) => self.get(name),
+            };
+            if let Some(span) = span {
+                dst.push_str(&input[span.bytes()]);
+            }
+        }
+        dst.push_str(replacement);
+    }
+}
+
+/// Split the group name (bare or in braces) off the start of what follows a `$`.
+fn group_ref(text: &str) -> Option<(&str, &str)> {
+    if let Some(braced) = text.strip_prefix('{') {
+        let end = braced.find('}')?;
+        return Some((&braced[..end], &braced[end + 1..]));
+    }
+    let end = text
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+        .unwrap_or(text.len());
+    if end == 0 {
+        None
+    } else {
+        Some(text.split_at(end))
+    }
+}
+
+/// See `Regex.captures(&str)`.
+pub(crate) fn captures<R: Regex>(regex: &mut R, input: &str) -> Option<Captures> {
+    let mut pos = Pos::default();
+    let context = regex.uses_context();
+    let mut chars = input.chars().peekable();
+    regex.initialize();
+    if context {
+        regex.context(None, chars.peek().copied());
+    }
+    regex.start_tagged(&Tags::new(pos), pos);
+    while let Some(ch) = chars.next() {
+        pos = pos.after(ch);
+        if context {
+            regex.context(Some(ch), chars.peek().copied());
+        }
+        regex.advance_tagged(ch, pos);
+        if regex.is_dead() {
+            return None;
+        }
+    }
+    regex
+        .accepted_tags(pos)
+        .map(|tags| Captures::new(&tags, pos))
+}
+
+/*********/
+/* Group */
+/*********/
+
+#[derive(Clone)]
+pub(crate) struct Group<P> {
+    name: Arc<str>,
+    state: P,
+}
+
+impl<P> Group<P> {
+    pub(crate) fn new(name: &str, regex: P) -> Group<P> {
+        Group {
+            name: name.into(),
+            state: regex,
+        }
+    }
+}
+
+impl<T: Symbol, P: Regex<T>> Regex<T> for Group<P> {
+    fn initialize(&mut self) {
+        self.state.initialize();
+    }
+
+    fn start(&mut self) {
+        self.state.start();
+    }
+
+    fn advance(&mut self, ch: T) {
+        self.state.advance(ch);
+    }
+
+    fn accepts(&self) -> bool {
+        self.state.accepts()
+    }
+
+    fn is_dead(&self) -> bool {
+        self.state.is_dead()
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.state.start_tagged(&tags.open(&self.name, pos), pos);
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        self.state.advance_tagged(ch, pos);
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        self.state
+            .accepted_tags(pos)
+            .map(|tags| tags.close(&self.name, pos))
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.state.is_saturated()
+    }
+
+    fn matches_every_char(&self) -> bool {
+        self.state.matches_every_char()
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.state.context(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        self.state.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Group(self.name.to_string(), Box::new(self.state.syntax()))
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        self.state.write_state(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::combinators::*;
+    use crate::Regex;
+
+    #[test]
+    fn captures() {
+        let digit = || char_range('0', '9');
+        let mut decimal = seq(
+            group("int", seq(digit(), star(digit()))),
+            maybe(seq(achar('.'), group("frac", star(digit())))),
+        );
+
+        let caps = decimal.captures("31.415").unwrap();
+        assert_eq!(caps.span().bytes(), 0..6);
+        assert_eq!(caps.get("int").unwrap().bytes(), 0..2);
+        assert_eq!(caps.get("frac").unwrap().bytes(), 3..6);
+
+        let caps = decimal.captures("31").unwrap();
+        assert_eq!(caps.get("int").unwrap().bytes(), 0..2);
+        assert_eq!(caps.get("frac"), None);
+
+        assert!(decimal.captures("3.1.4").is_none());
+        assert!(decimal.captures("").is_none());
+
+        // Byte and char offsets differ once there is non-ascii input.
+        let mut greeting = seq(
+            star(char_range('α', 'ω')),
+            group("name", star(char_range('a', 'z'))),
+        );
+        let name = greeting.captures("γειαbob").unwrap().get("name").unwrap();
+        assert_eq!(name.chars(), 4..7);
+        assert_eq!(name.bytes(), 8..11);
+
+        // The last iteration of a repeated group wins.
+        let mut words = star(seq(group("word", star(char_range('a', 'z'))), achar(' ')));
+        let caps = words.captures("ab cde f ").unwrap();
+        assert_eq!(caps.get("word").unwrap().bytes(), 7..8);
+        assert_eq!(
+            caps.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            ["word"]
+        );
+
+        // The left side of an `alt` wins a tie.
+        let mut either = alt(group("left", achar('x')), group("right", dot()));
+        let caps = either.captures("x").unwrap();
+        assert!(caps.get("left").is_some());
+        assert!(caps.get("right").is_none());
+        let caps = either.captures("y").unwrap();
+        assert!(caps.get("left").is_none());
+        assert!(caps.get("right").is_some());
+    }
+}
@@ -0,0 +1,231 @@
+use super::predicates::CharSet;
+use super::{Pos, Predicate, Regex, Syntax, Tags};
+use std::sync::{Arc, OnceLock};
+
+/// Unicode full case folding, approximated using the standard library's case mappings: a char's
+/// folding is its uppercase mapping, lowercased, repeated until it stops changing (e.g. `ẞ` → `ß` →
+/// `ss`). Two strings are equal up to case iff their foldings are equal.
+pub(crate) fn fold(ch: char, out: &mut Vec<char>) {
+    if ch.is_ascii() {
+        out.push(ch.to_ascii_lowercase());
+        return;
+    }
+    let start = out.len();
+    out.extend(ch.to_uppercase().flat_map(char::to_lowercase));
+    loop {
+        let folded: Vec<char> = out[start..]
+            .iter()
+            .flat_map(|ch| ch.to_uppercase().flat_map(char::to_lowercase))
+            .collect();
+        if folded[..] == out[start..] {
+            return;
+        }
+        out.truncate(start);
+        out.extend(folded);
+    }
+}
+
+/// The chars whose folding isn't a char in their own simple case folding class (see
+/// `CharSet.case_fold`): those that fold to several chars, like `ß`, and a few that the standard
+/// library's tables and the simple case folding tables disagree on, like `ı`. Found by checking
+/// every char, the first time it's needed.
+pub(crate) fn irregular_folds() -> &'static CharSet {
+    static IRREGULAR: OnceLock<CharSet> = OnceLock::new();
+    IRREGULAR.get_or_init(|| {
+        let mut irregular = vec![];
+        let mut folded = vec![];
+        for ch in ('\0'..=char::MAX).filter(|&ch| !ch.is_ascii()) {
+            // Skip the (many) chars without case mappings, which fold to themselves.
+            if ch.to_uppercase().eq([ch]) && ch.to_lowercase().eq([ch]) {
+                continue;
+            }
+            folded.clear();
+            fold(ch, &mut folded);
+            let regular = match folded[..] {
+                [folded] => CharSet::new(vec![(folded, folded)]).case_fold().matches(ch),
+                _ => false,
+            };
+            if !regular {
+                irregular.push((ch, ch));
+            }
+        }
+        CharSet::new(irregular)
+    })
+}
+
+/// Match exactly the strings whose case folding is the same as `target`'s. Unlike comparing one
+/// char at a time, this handles chars that fold to several chars, so that `ß` matches `SS`.
+///
+/// The state is the set of positions in the (folded) target that some tracked string has matched
+/// up to. Advancing by `ch` moves each position forward by the length of `ch`'s folding, if the
+/// folding is next in the target.
+#[derive(Clone)]
+pub(crate) struct FoldedLiteral {
+    target: Arc<[char]>,
+    active: Vec<bool>,
+    tags: Vec<Option<Tags>>,
+    scratch: Vec<char>,
+}
+
+impl FoldedLiteral {
+    pub(crate) fn new(target: &str) -> FoldedLiteral {
+        let mut folded = vec![];
+        for ch in target.chars() {
+            fold(ch, &mut folded);
+        }
+        FoldedLiteral {
+            active: vec![false; folded.len() + 1],
+            tags: vec![None; folded.len() + 1],
+            target: folded.into(),
+            scratch: vec![],
+        }
+    }
+
+    /// Match `self`, then `other`.
+    pub(crate) fn concat(&self, other: &FoldedLiteral) -> FoldedLiteral {
+        let target: Vec<char> = self
+            .target
+            .iter()
+            .chain(other.target.iter())
+            .copied()
+            .collect();
+        FoldedLiteral {
+            active: vec![false; target.len() + 1],
+            tags: vec![None; target.len() + 1],
+            target: target.into(),
+            scratch: vec![],
+        }
+    }
+
+    /// Fold `ch` into `self.scratch`, returning how many chars its folding has.
+    fn fold_input(&mut self, ch: char) -> usize {
+        self.scratch.clear();
+        fold(ch, &mut self.scratch);
+        self.scratch.len()
+    }
+
+    /// Whether the folded input char comes next in the target after position `i`.
+    fn moves(&self, i: usize) -> bool {
+        self.target[i..].starts_with(&self.scratch)
+    }
+}
+
+impl Regex for FoldedLiteral {
+    fn initialize(&mut self) {
+        self.active.iter_mut().for_each(|active| *active = false);
+        self.tags.iter_mut().for_each(|tags| *tags = None);
+    }
+
+    fn start(&mut self) {
+        self.active[0] = true;
+    }
+
+    fn advance(&mut self, ch: char) {
+        let len = self.fold_input(ch);
+        // Go backwards, so that each position is moved before anything is moved onto it.
+        for i in (0..self.active.len()).rev() {
+            if self.active[i] {
+                self.active[i] = false;
+                if self.moves(i) {
+                    self.active[i + len] = true;
+                }
+            }
+        }
+    }
+
+    fn accepts(&self) -> bool {
+        self.active[self.target.len()]
+    }
+
+    fn is_dead(&self) -> bool {
+        self.active.iter().all(|active| !active)
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::LiteralCi(self.target.iter().collect())
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, _pos: Pos) {
+        self.active[0] = true;
+        self.tags[0] = Tags::prefer(self.tags[0].take(), Some(tags.clone()));
+    }
+
+    fn advance_tagged(&mut self, ch: char, _pos: Pos) {
+        let len = self.fold_input(ch);
+        for i in (0..self.active.len()).rev() {
+            let tags = self.tags[i].take();
+            if self.active[i] {
+                self.active[i] = false;
+                if self.moves(i) {
+                    self.active[i + len] = true;
+                    self.tags[i + len] = tags;
+                }
+            }
+        }
+    }
+
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        self.tags[self.target.len()].clone()
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.extend(self.active.iter().map(|active| *active as u8));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::combinators::*;
+    use crate::Regex;
+
+    #[test]
+    fn case_insensitive() {
+        let mut sharp_s = achar_ci('ß');
+        assert!(sharp_s.is_match("ß"));
+        assert!(sharp_s.is_match("ẞ"));
+        assert!(sharp_s.is_match("ss"));
+        assert!(sharp_s.is_match("sS"));
+        assert!(!sharp_s.is_match("s"));
+        assert!(!sharp_s.is_match("sss"));
+
+        let mut s = achar_ci('s');
+        assert!(s.is_match("S"));
+        assert!(s.is_match("ſ"));
+        assert!(!s.is_match("ß"));
+
+        let mut street = literal_ci("Straße");
+        assert!(street.is_match("STRASSE"));
+        assert!(street.is_match("strasse"));
+        assert!(street.is_match("ſtraße"));
+        assert!(!street.is_match("strase"));
+        let mut street = literal_ci("STRASSE");
+        assert!(street.is_match("Straße"));
+        assert!(street.is_match("STRAẞE"));
+
+        let mut dotted = literal_ci("İstanbul");
+        assert!(dotted.is_match("i\u{307}stanbul"));
+        assert!(!dotted.is_match("istanbul"));
+
+        let mut sigma = plus(achar_ci('σ'));
+        assert!(sigma.is_match("Σσς"));
+
+        // Ranges that cross case boundaries.
+        let mut odd = char_range_ci('Z', 'a');
+        assert!(odd.is_match("_"));
+        assert!(odd.is_match("z"));
+        assert!(odd.is_match("A"));
+        assert!(!odd.is_match("b"));
+        assert!(!odd.is_match("Y"));
+        let mut letters = plus(char_range_ci('a', 'z'));
+        assert!(letters.is_match("ſKelvinK"));
+        assert!(!letters.is_match("ß"));
+        let mut greek = plus(char_range_ci('α', 'ω'));
+        assert!(greek.is_match("ΑΒΓαβγ"));
+
+        let caps = seq(achar('<'), seq(group("tag", literal_ci("ss")), achar('>')))
+            .captures("<ß>")
+            .unwrap();
+        assert_eq!(caps.get("tag").unwrap().bytes(), 1..3);
+    }
+}
@@ -0,0 +1,423 @@
+use super::explore::{Found, Interner};
+use super::{search, Pos, Regex, Syntax, Tags};
+use std::collections::HashMap;
+
+/// Speed up matching with `regex` by caching its states and the transitions between them, as they
+/// are explored. See `Dfa`.
+pub fn compile_dfa<R: Regex>(regex: R) -> Dfa<R> {
+    Dfa::new(regex)
+}
+
+/// A regex that caches the states of another regex, making it a lazily built DFA.
+///
+/// Running a combinator regex redoes the same work every time it sees the same char in the same
+/// state. A `Dfa` instead identifies states by their snapshots (see `Regex.write_state`), and
+/// remembers which state each char leads to, so that once a transition has been seen, following
+/// it again is a single lookup.
+///
+/// At most `capacity` states are cached. Beyond that, or if the regex doesn't support snapshots,
+/// the `Dfa` falls back to running the regex directly, until it reaches a cached state again. The
+/// tagged methods (used by `captures` and `find`) always run the regex directly, since the cached
+/// states don't have tags. If the regex uses the context (see `Regex.context`), each context is
+/// cached as a transition too.
+///
+/// Cloning a `Dfa` clones its cache, so avoid putting one inside combinators (like `repeat`) that
+/// make many copies of their parts.
+#[derive(Clone)]
+pub struct Dfa<R: Regex> {
+    states: Vec<State<R>>,
+    /// The ids of the cached states, which also knows whether the regex supports snapshots.
+    interner: Interner,
+    capacity: usize,
+    current: Current<R>,
+    /// Whether the regex uses the context.
+    context: bool,
+}
+
+/// The default maximum number of cached states.
+const DEFAULT_CAPACITY: usize = 1000;
+
+/// A transition that hasn't been cached yet.
+const UNKNOWN: u32 = u32::MAX;
+
+#[derive(Clone)]
+enum Current<R> {
+    Cached(u32),
+    Running(R),
+}
+
+#[derive(Clone)]
+struct State<R> {
+    regex: R,
+    accepts: bool,
+    is_dead: bool,
+    is_saturated: bool,
+    start: u32,
+    ascii: [u32; 128],
+    other: HashMap<char, u32>,
+    contexts: HashMap<(Option<char>, Option<char>), u32>,
+}
+
+#[derive(Clone, Copy)]
+enum Event {
+    Start,
+    Advance(char),
+    Context(Option<char>, Option<char>),
+}
+
+impl Event {
+    fn apply<R: Regex>(self, regex: &mut R) {
+        match self {
+            Event::Start => regex.start(),
+            Event::Advance(ch) => regex.advance(ch),
+            Event::Context(prev, next) => regex.context(prev, next),
+        }
+    }
+}
+
+impl<R: Regex> Dfa<R> {
+    fn new(mut regex: R) -> Dfa<R> {
+        regex.initialize();
+        let mut dfa = Dfa {
+            states: vec![],
+            interner: Interner::new(),
+            capacity: DEFAULT_CAPACITY,
+            current: Current::Cached(0),
+            context: regex.uses_context(),
+        };
+        dfa.current = match dfa.intern(regex) {
+            Ok(id) => Current::Cached(id),
+            Err(regex) => Current::Running(regex),
+        };
+        dfa
+    }
+
+    /// Set the maximum number of states to cache (at least 1). The default is 1000.
+    pub fn with_capacity(mut self, capacity: usize) -> Dfa<R> {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// The number of states cached so far.
+    pub fn cached_states(&self) -> usize {
+        self.states.len()
+    }
+
+    /// The id of the cached state that `regex` is in, caching it if it's new and there's room.
+    fn intern(&mut self, regex: R) -> Result<u32, R> {
+        if !self.interner.snapshots {
+            return Err(regex);
+        }
+        match self.interner.find(|out| regex.write_state(out)) {
+            Found::Old(id) => return Ok(id as u32),
+            Found::New if self.states.len() < self.capacity => (),
+            Found::New | Found::Unknown => return Err(regex),
+        }
+        let id = self.states.len() as u32;
+        self.interner.insert(id as usize);
+        self.states.push(State {
+            accepts: regex.accepts(),
+            is_dead: regex.is_dead(),
+            is_saturated: regex.is_saturated(),
+            regex,
+            start: UNKNOWN,
+            ascii: [UNKNOWN; 128],
+            other: HashMap::new(),
+            contexts: HashMap::new(),
+        });
+        Ok(id)
+    }
+
+    /// The cached transition from the current state on `event`, if there is one.
+    fn cached_transition(&self, event: Event) -> Option<u32> {
+        let state = match self.current {
+            Current::Cached(id) => &self.states[id as usize],
+            Current::Running(_) => return None,
+        };
+        let next = match event {
+            Event::Start => state.start,
+            Event::Advance(ch) if ch.is_ascii() => state.ascii[ch as usize],
+            Event::Advance(ch) => state.other.get(&ch).copied().unwrap_or(UNKNOWN),
+            Event::Context(prev, next) => state
+                .contexts
+                .get(&(prev, next))
+                .copied()
+                .unwrap_or(UNKNOWN),
+        };
+        if next == UNKNOWN {
+            None
+        } else {
+            Some(next)
+        }
+    }
+
+    fn transition(&mut self, event: Event) {
+        if let Some(next) = self.cached_transition(event) {
+            self.current = Current::Cached(next);
+            return;
+        }
+        let current = std::mem::replace(&mut self.current, Current::Cached(0));
+        self.current = match current {
+            Current::Cached(id) => {
+                let mut regex = self.states[id as usize].regex.clone();
+                event.apply(&mut regex);
+                match self.intern(regex) {
+                    Ok(next) => {
+                        let state = &mut self.states[id as usize];
+                        match event {
+                            Event::Start => state.start = next,
+                            Event::Advance(ch) if ch.is_ascii() => state.ascii[ch as usize] = next,
+                            Event::Advance(ch) => {
+                                state.other.insert(ch, next);
+                            }
+                            Event::Context(prev, next_ch) => {
+                                state.contexts.insert((prev, next_ch), next);
+                            }
+                        }
+                        Current::Cached(next)
+                    }
+                    Err(regex) => Current::Running(regex),
+                }
+            }
+            Current::Running(mut regex) => {
+                event.apply(&mut regex);
+                match self.intern(regex) {
+                    Ok(id) => Current::Cached(id),
+                    Err(regex) => Current::Running(regex),
+                }
+            }
+        };
+    }
+
+    /// The regex, in the current state, to run directly.
+    fn running(&mut self) -> &mut R {
+        if let Current::Cached(id) = self.current {
+            self.current = Current::Running(self.states[id as usize].regex.clone());
+        }
+        match &mut self.current {
+            Current::Running(regex) => regex,
+            Current::Cached(_) => unreachable!(),
+        }
+    }
+
+    fn regex(&self) -> &R {
+        match &self.current {
+            Current::Cached(id) => &self.states[*id as usize].regex,
+            Current::Running(regex) => regex,
+        }
+    }
+}
+
+impl<R: Regex> Regex for Dfa<R> {
+    fn initialize(&mut self) {
+        if self.states.is_empty() {
+            self.running().initialize();
+        } else {
+            // The first state is the initial one.
+            self.current = Current::Cached(0);
+        }
+    }
+
+    fn start(&mut self) {
+        self.transition(Event::Start);
+    }
+
+    fn advance(&mut self, ch: char) {
+        self.transition(Event::Advance(ch));
+    }
+
+    fn accepts(&self) -> bool {
+        match &self.current {
+            Current::Cached(id) => self.states[*id as usize].accepts,
+            Current::Running(regex) => regex.accepts(),
+        }
+    }
+
+    fn is_dead(&self) -> bool {
+        match &self.current {
+            Current::Cached(id) => self.states[*id as usize].is_dead,
+            Current::Running(regex) => regex.is_dead(),
+        }
+    }
+
+    fn is_saturated(&self) -> bool {
+        match &self.current {
+            Current::Cached(id) => self.states[*id as usize].is_saturated,
+            Current::Running(regex) => regex.is_saturated(),
+        }
+    }
+
+    fn matches_every_char(&self) -> bool {
+        self.regex().matches_every_char()
+    }
+
+    fn context(&mut self, prev: Option<char>, next: Option<char>) {
+        match &mut self.current {
+            _ if !self.context => (),
+            // The regex may be running tagged, so don't lose its tags by interning it.
+            Current::Running(regex) => regex.context(prev, next),
+            Current::Cached(_) => self.transition(Event::Context(prev, next)),
+        }
+    }
+
+    fn uses_context(&self) -> bool {
+        self.context
+    }
+
+    fn syntax(&self) -> Syntax {
+        match &self.current {
+            Current::Cached(id) => self.states[*id as usize].regex.syntax(),
+            Current::Running(regex) => regex.syntax(),
+        }
+    }
+
+    fn is_match(&mut self, input: &str) -> bool {
+        if self.context {
+            return search::is_match_in_context(self, input.chars());
+        }
+        self.initialize();
+        self.start();
+        for ch in input.chars() {
+            // The fast path: following a cached transition.
+            if let (Current::Cached(id), true) = (&self.current, ch.is_ascii()) {
+                let state = &self.states[*id as usize];
+                if state.is_saturated {
+                    return true;
+                }
+                let next = state.ascii[ch as usize];
+                if next != UNKNOWN {
+                    if self.states[next as usize].is_dead {
+                        return false;
+                    }
+                    self.current = Current::Cached(next);
+                    continue;
+                }
+            }
+            if self.is_saturated() {
+                return true;
+            }
+            self.advance(ch);
+            if self.is_dead() {
+                return false;
+            }
+        }
+        self.accepts()
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.running().start_tagged(tags, pos);
+    }
+
+    fn advance_tagged(&mut self, ch: char, pos: Pos) {
+        self.running().advance_tagged(ch, pos);
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        self.regex().accepted_tags(pos)
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        self.regex().write_state(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::{BoxedRegex, Pattern};
+
+    // Matches strings with an odd number of chars, and doesn't support snapshots.
+    #[derive(Clone)]
+    struct Odd {
+        even: bool,
+        odd: bool,
+    }
+
+    impl Regex for Odd {
+        fn initialize(&mut self) {
+            self.even = false;
+            self.odd = false;
+        }
+
+        fn start(&mut self) {
+            self.even = true;
+        }
+
+        fn advance(&mut self, _ch: char) {
+            std::mem::swap(&mut self.even, &mut self.odd);
+        }
+
+        fn accepts(&self) -> bool {
+            self.odd
+        }
+
+        fn is_dead(&self) -> bool {
+            !self.even && !self.odd
+        }
+    }
+
+    #[test]
+    fn dfa() {
+        let regexes: Vec<BoxedRegex> = vec![
+            boxed(seq(
+                alt(achar('a'), seq(achar('b'), star(char_range('a', 'c')))),
+                maybe(seq(achar('.'), star(achar('a')))),
+            )),
+            boxed(Pattern::new("(a|b.){2,3}|c*").unwrap()),
+            boxed(seq(plus(literal_ci("ab")), star(dot()))),
+            boxed(alt(
+                achar('c'),
+                seq(
+                    achar('a'),
+                    Odd {
+                        even: false,
+                        odd: false,
+                    },
+                ),
+            )),
+        ];
+        let mut inputs = vec![String::new()];
+        for len in 1..=6 {
+            for mut n in 0..4usize.pow(len) {
+                let mut input = String::new();
+                for _ in 0..len {
+                    input.push(['a', 'b', 'c', '.'][n % 4]);
+                    n /= 4;
+                }
+                inputs.push(input);
+            }
+        }
+        for mut regex in regexes {
+            let mut dfa = compile_dfa(regex.clone());
+            let mut small = compile_dfa(regex.clone()).with_capacity(2);
+            for input in &inputs {
+                let expected = regex.is_match(input);
+                assert_eq!(dfa.is_match(input), expected, "{:?}", input);
+                assert_eq!(small.is_match(input), expected, "{:?}", input);
+                assert_eq!(dfa.find(input), regex.find(input), "{:?}", input);
+            }
+            assert!(small.cached_states() <= 2);
+        }
+
+        // A handful of states are enough for any input.
+        let mut number = compile_dfa(Pattern::new(r"(0|[1-9][0-9]*)(\.[0-9]*)?").unwrap());
+        assert!(number.is_match("3.14159265358979"));
+        assert!(!number.is_match("3.14.15"));
+        assert!(number.cached_states() < 10);
+
+        // Without snapshots, nothing is cached, but it still works.
+        let mut odd = compile_dfa(Odd {
+            even: false,
+            odd: false,
+        });
+        assert!(odd.is_match("abc"));
+        assert!(!odd.is_match("ab"));
+        assert_eq!(odd.cached_states(), 0);
+
+        let caps = compile_dfa(seq(group("a", plus(achar('a'))), achar('b')))
+            .captures("aab")
+            .unwrap();
+        assert_eq!(caps.get("a").unwrap().bytes(), 0..2);
+    }
+}
@@ -0,0 +1,193 @@
+//! Property tests that build random combinator trees, along with the same regex in the syntax of
+//! the `regex` crate, and check that the two agree on random inputs. When they don't, proptest
+//! shrinks the tree (and the inputs) down to a minimal failing case.
+
+use crate::combinators::*;
+use crate::weighted::weigh;
+use crate::{compile_dfa, BoxedRegex, MatchKind, Pattern, Regex};
+use proptest::prelude::*;
+use proptest::sample::select;
+
+/// The chars that trees and inputs are built from. A small alphabet makes matches likely, and
+/// the space, newline and non-ascii char exercise the assertions and byte offsets.
+const ALPHABET: &[char] = &['a', 'b', 'c', ' ', '\n', 'é'];
+
+#[derive(Clone, Debug)]
+enum Tree {
+    Empty,
+    Dot,
+    Char(char),
+    Range(char, char),
+    WordBoundary,
+    LineStart,
+    LineEnd,
+    Seq(Box<Tree>, Box<Tree>),
+    Alt(Box<Tree>, Box<Tree>),
+    Star(Box<Tree>),
+    Plus(Box<Tree>),
+    Maybe(Box<Tree>),
+    Repeat(Box<Tree>, usize, Option<usize>),
+    Group(Box<Tree>),
+}
+
+impl Tree {
+    /// The regex, with its groups named `1`, `2`, ... in the same order as the `regex` crate
+    /// numbers them, starting after `groups`.
+    fn to_regex(&self, groups: &mut usize) -> BoxedRegex {
+        match self {
+            Tree::Empty => boxed(empty()),
+            Tree::Dot => boxed(dot()),
+            Tree::Char(ch) => boxed(achar(*ch)),
+            Tree::Range(min, max) => boxed(char_range(*min, *max)),
+            Tree::WordBoundary => boxed(word_boundary()),
+            Tree::LineStart => boxed(line_start()),
+            Tree::LineEnd => boxed(line_end()),
+            Tree::Seq(first, second) => {
+                let first = first.to_regex(groups);
+                boxed(seq(first, second.to_regex(groups)))
+            }
+            Tree::Alt(left, right) => {
+                let left = left.to_regex(groups);
+                boxed(alt(left, right.to_regex(groups)))
+            }
+            Tree::Star(tree) => boxed(star(tree.to_regex(groups))),
+            Tree::Plus(tree) => boxed(plus(tree.to_regex(groups))),
+            Tree::Maybe(tree) => boxed(maybe(tree.to_regex(groups))),
+            Tree::Repeat(tree, min, max) => boxed(repeat(tree.to_regex(groups), *min, *max)),
+            Tree::Group(tree) => {
+                *groups += 1;
+                let name = groups.to_string();
+                boxed(group(&name, tree.to_regex(groups)))
+            }
+        }
+    }
+
+    /// Whether the tree has no alternations, in which case the `regex` crate should find the same
+    /// matches and groups as a leftmost-first search. (With them, it stops repeating an
+    /// alternation after an iteration that matched nothing, where this crate doesn't.)
+    fn is_alt_free(&self) -> bool {
+        match self {
+            Tree::Alt(..) => false,
+            Tree::Seq(first, second) => first.is_alt_free() && second.is_alt_free(),
+            Tree::Star(tree)
+            | Tree::Plus(tree)
+            | Tree::Maybe(tree)
+            | Tree::Repeat(tree, ..)
+            | Tree::Group(tree) => tree.is_alt_free(),
+            _ => true,
+        }
+    }
+
+    /// The same regex, in the `regex` crate's syntax.
+    fn to_pattern(&self) -> String {
+        match self {
+            Tree::Empty => "(?:)".to_owned(),
+            Tree::Dot => "(?s:.)".to_owned(),
+            Tree::Char(ch) => regex::escape(&ch.to_string()),
+            Tree::Range(min, max) => format!(
+                "[{}-{}]",
+                regex::escape(&min.to_string()),
+                regex::escape(&max.to_string())
+            ),
+            Tree::WordBoundary => r"\b".to_owned(),
+            Tree::LineStart => "(?m:^)".to_owned(),
+            Tree::LineEnd => "(?m:$)".to_owned(),
+            Tree::Seq(first, second) => format!("{}{}", first.to_pattern(), second.to_pattern()),
+            Tree::Alt(left, right) => format!("(?:{}|{})", left.to_pattern(), right.to_pattern()),
+            Tree::Star(tree) => format!("(?:{})*", tree.to_pattern()),
+            Tree::Plus(tree) => format!("(?:{})+", tree.to_pattern()),
+            Tree::Maybe(tree) => format!("(?:{})?", tree.to_pattern()),
+            Tree::Repeat(tree, min, None) => format!("(?:{}){{{},}}", tree.to_pattern(), min),
+            Tree::Repeat(tree, min, Some(max)) => {
+                format!("(?:{}){{{},{}}}", tree.to_pattern(), min, max)
+            }
+            Tree::Group(tree) => format!("({})", tree.to_pattern()),
+        }
+    }
+}
+
+fn tree() -> impl Strategy<Value = Tree> {
+    let leaf = prop_oneof![
+        Just(Tree::Empty),
+        Just(Tree::Dot),
+        select(ALPHABET).prop_map(Tree::Char),
+        (select(ALPHABET), select(ALPHABET)).prop_map(|(a, b)| Tree::Range(a.min(b), a.max(b))),
+        Just(Tree::WordBoundary),
+        Just(Tree::LineStart),
+        Just(Tree::LineEnd),
+    ];
+    leaf.prop_recursive(5, 24, 2, |inner| {
+        prop_oneof![
+            (inner.clone(), inner.clone())
+                .prop_map(|(first, second)| Tree::Seq(Box::new(first), Box::new(second))),
+            (inner.clone(), inner.clone())
+                .prop_map(|(left, right)| Tree::Alt(Box::new(left), Box::new(right))),
+            inner.clone().prop_map(|tree| Tree::Star(Box::new(tree))),
+            inner.clone().prop_map(|tree| Tree::Plus(Box::new(tree))),
+            inner.clone().prop_map(|tree| Tree::Maybe(Box::new(tree))),
+            (inner.clone(), 0..3usize, prop::option::of(0..3usize)).prop_map(
+                |(tree, min, extra)| Tree::Repeat(Box::new(tree), min, extra.map(|n| min + n))
+            ),
+            inner.prop_map(|tree| Tree::Group(Box::new(tree))),
+        ]
+    })
+}
+
+fn inputs() -> impl Strategy<Value = Vec<String>> {
+    let input = prop::collection::vec(select(ALPHABET), 0..8)
+        .prop_map(|chars| chars.into_iter().collect::<String>());
+    prop::collection::vec(input, 1..16)
+}
+
+proptest! {
+    #[test]
+    fn agrees_with_regex_crate(tree in tree(), inputs in inputs()) {
+        let pattern = tree.to_pattern();
+        let expected = regex::Regex::new(&format!("^(?:{})$", pattern)).unwrap();
+        let unanchored = regex::Regex::new(&pattern).unwrap();
+        let mut groups = 0;
+        let mut regex = tree.to_regex(&mut groups);
+        let mut dfa = compile_dfa(regex.clone());
+        // Printing the regex should give an equivalent pattern.
+        let printed = regex.syntax().to_string();
+        let mut reparsed = Pattern::new(&printed).unwrap();
+        for input in &inputs {
+            let expected = expected.is_match(input);
+            prop_assert_eq!(regex.is_match(input), expected, "{} on {:?}", pattern, input);
+            prop_assert_eq!(dfa.is_match(input), expected, "DFA: {} on {:?}", pattern, input);
+            prop_assert_eq!(reparsed.is_match(input), expected, "{} on {:?}", printed, input);
+            let weighed = weigh(&regex, input, |_, _| true);
+            prop_assert_eq!(weighed, Some(expected), "weighed: {} on {:?}", pattern, input);
+
+            // Leftmost-longest searches should find a match that starts in the same place as the
+            // `regex` crate's, and (without alternations) leftmost-first ones the same match.
+            let expected = unanchored.captures(input);
+            prop_assert_eq!(
+                regex.find(input).map(|range| range.start),
+                expected.as_ref().map(|caps| caps.get(0).unwrap().start()),
+                "find: {} on {:?}", pattern, input
+            );
+            if !tree.is_alt_free() {
+                continue;
+            }
+            let found = regex
+                .find_iter(input)
+                .with_kind(MatchKind::LeftmostFirst)
+                .next_captures(true);
+            prop_assert_eq!(
+                found.as_ref().map(|caps| caps.span().bytes()),
+                expected.as_ref().map(|caps| caps.get(0).unwrap().range()),
+                "leftmost-first: {} on {:?}", pattern, input
+            );
+            if let (Some(found), Some(expected)) = (found, expected) {
+                for i in 1..=groups {
+                    prop_assert_eq!(
+                        found.get(&i.to_string()).map(|span| span.bytes()),
+                        expected.get(i).map(|group| group.range()),
+                        "group {}: {} on {:?}", i, pattern, input
+                    );
+                }
+            }
+        }
+    }
+}
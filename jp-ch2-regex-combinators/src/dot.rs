@@ -0,0 +1,295 @@
+//! Draw regexes as automata, in the DOT language of [Graphviz](https://graphviz.org/), e.g. to
+//! render with `dot -Tsvg`.
+//!
+//! `nfa` draws the Thompson NFA that the combinators implicitly build, and `dfa` the DFA that
+//! running the regex explores.
+
+use super::explore::{Alphabet, Explorer};
+use super::predicates::CharSet;
+use super::syntax::SIZE_LIMIT;
+use super::{Regex, Syntax};
+use std::fmt::Write;
+
+/// The Thompson NFA of `regex`, built from its `Regex.syntax()`. Each char class, assertion, or
+/// case-insensitive literal is one edge, and parts without syntax (like user-defined combinators)
+/// are one edge labelled with their name. Repeats are unrolled, unless that would make the NFA
+/// too large, in which case they are one edge labelled with their syntax. Unlabelled edges are
+/// ε-transitions.
+pub fn nfa(regex: &impl Regex) -> String {
+    let mut nfa = Nfa::default();
+    let start = nfa.state();
+    let end = nfa.build(&regex.syntax(), start);
+
+    let mut out = header("nfa");
+    writeln!(out, "    start -> {};", start).unwrap();
+    writeln!(out, "    {} [shape=doublecircle];", end).unwrap();
+    for (from, to, label) in &nfa.edges {
+        match label {
+            Some(label) => writeln!(out, "    {} -> {} [label=\"{}\"];", from, to, escape(label)),
+            None => writeln!(out, "    {} -> {} [label=\"ε\"];", from, to),
+        }
+        .unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The DFA of `regex` over `alphabet`: every state reachable by `start()` and then `advance`ing
+/// over chars in `alphabet`, with edges for each char. States are told apart by their snapshots
+/// (see `Regex.write_state`). Accepting states are drawn with a double circle, and the dead states
+/// are drawn as one, dashed and gray, with its edges left out.
+///
+/// Returns `None` if the regex doesn't support snapshots, or uses the context (see
+/// `Regex.context`), since then its states don't just depend on the chars read.
+pub fn dfa<R: Regex>(regex: R, alphabet: &[char]) -> Option<String> {
+    if regex.uses_context() {
+        return None;
+    }
+    let mut explorer = Explorer::start(&regex, Alphabet::chars(alphabet));
+    // The number that each state is drawn with, in the order they are found, and that of the dead
+    // state, which the explorer leaves out, if some char leads to it.
+    let mut numbers = vec![0];
+    let mut dead = None;
+    let mut drawn = 1;
+    let mut accepting = vec![];
+    let mut edges = vec![];
+    let mut from = 0;
+    while from < numbers.len() {
+        if !explorer.has_snapshots() {
+            return None;
+        }
+        if explorer.accepts(from) {
+            accepting.push(numbers[from]);
+        }
+        if explorer.stepped(from, None).is_dead() {
+            dead = Some(numbers[from]);
+            from += 1;
+            continue;
+        }
+        // Group the chars that lead to the same state into one edge.
+        let mut targets: Vec<(usize, Vec<char>)> = vec![];
+        for (i, &ch) in alphabet.iter().enumerate() {
+            let to = match explorer.child(from, i) {
+                Some(to) if to < numbers.len() => numbers[to],
+                Some(_) => {
+                    numbers.push(drawn);
+                    drawn += 1;
+                    drawn - 1
+                }
+                None if dead.is_none() => {
+                    dead = Some(drawn);
+                    drawn += 1;
+                    drawn - 1
+                }
+                None => dead.unwrap(),
+            };
+            match targets.iter_mut().find(|(target, _)| *target == to) {
+                Some((_, chars)) => chars.push(ch),
+                None => targets.push((to, vec![ch])),
+            }
+        }
+        for (to, chars) in targets {
+            let label = Syntax::Class(CharSet::new(chars.into_iter().map(|ch| (ch, ch))));
+            edges.push((numbers[from], to, label.to_string()));
+        }
+        from += 1;
+    }
+
+    let mut out = header("dfa");
+    writeln!(out, "    start -> 0;").unwrap();
+    for number in 0..drawn {
+        if accepting.contains(&number) {
+            writeln!(out, "    {} [shape=doublecircle];", number).unwrap();
+        } else if dead == Some(number) {
+            writeln!(out, "    {} [style=dashed, color=gray];", number).unwrap();
+        }
+    }
+    for (from, to, label) in &edges {
+        writeln!(out, "    {} -> {} [label=\"{}\"];", from, to, escape(label)).unwrap();
+    }
+    out.push_str("}\n");
+    Some(out)
+}
+
+fn header(name: &str) -> String {
+    format!(
+        "digraph {} {{\n    rankdir=LR;\n    node [shape=circle];\n    start [shape=point];\n",
+        name
+    )
+}
+
+// Escape a label for a double-quoted DOT string, where backslashes start escape sequences.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/*******/
+/* NFA */
+/*******/
+
+#[derive(Default)]
+struct Nfa {
+    states: usize,
+    // (from, to, label), where edges without a label are ε-transitions.
+    edges: Vec<(usize, usize, Option<String>)>,
+}
+
+impl Nfa {
+    fn state(&mut self) -> usize {
+        self.states += 1;
+        self.states - 1
+    }
+
+    fn epsilon(&mut self, from: usize, to: usize) {
+        self.edges.push((from, to, None));
+    }
+
+    /// Add the states and edges for `syntax`, starting from state `from`, and return the state
+    /// they end at.
+    fn build(&mut self, syntax: &Syntax, from: usize) -> usize {
+        match syntax {
+            Syntax::Empty => from,
+            // Parts that a Thompson NFA can't express are drawn as one edge, labelled with their
+            // syntax.
+            Syntax::Class(_)
+            | Syntax::LiteralCi(_)
+            | Syntax::Assert(_)
+            | Syntax::Intersect(..)
+            | Syntax::Complement(_)
+            | Syntax::Fuzzy(..)
+            | Syntax::Opaque(_) => {
+                let to = self.state();
+                self.edges.push((from, to, Some(syntax.to_string())));
+                to
+            }
+            Syntax::Seq(first, second) => {
+                let middle = self.build(first, from);
+                self.build(second, middle)
+            }
+            Syntax::Alt(left, right) => {
+                let end = self.state();
+                for part in [left, right] {
+                    let start = self.state();
+                    self.epsilon(from, start);
+                    let part_end = self.build(part, start);
+                    self.epsilon(part_end, end);
+                }
+                end
+            }
+            Syntax::Star(regex) => self.star(regex, from),
+            Syntax::Maybe(regex) => self.maybe(regex, from),
+            Syntax::Repeat(..) if syntax.size() > SIZE_LIMIT => {
+                let to = self.state();
+                self.edges.push((from, to, Some(syntax.to_string())));
+                to
+            }
+            Syntax::Repeat(regex, min, max) => {
+                let mut end = from;
+                for _ in 0..*min {
+                    end = self.build(regex, end);
+                }
+                match max {
+                    None => self.star(regex, end),
+                    Some(max) => {
+                        for _ in *min..*max {
+                            end = self.maybe(regex, end);
+                        }
+                        end
+                    }
+                }
+            }
+            Syntax::Group(_, regex) => self.build(regex, from),
+        }
+    }
+
+    fn star(&mut self, regex: &Syntax, from: usize) -> usize {
+        let hub = self.state();
+        self.epsilon(from, hub);
+        let start = self.state();
+        self.epsilon(hub, start);
+        let regex_end = self.build(regex, start);
+        self.epsilon(regex_end, hub);
+        let end = self.state();
+        self.epsilon(hub, end);
+        end
+    }
+
+    fn maybe(&mut self, regex: &Syntax, from: usize) -> usize {
+        let start = self.state();
+        self.epsilon(from, start);
+        let regex_end = self.build(regex, start);
+        let end = self.state();
+        self.epsilon(regex_end, end);
+        self.epsilon(from, end);
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Pattern;
+
+    #[test]
+    fn graphviz() {
+        let lines = |dot: &str| dot.lines().map(str::to_owned).collect::<Vec<_>>();
+        let header = [
+            "    rankdir=LR;",
+            "    node [shape=circle];",
+            "    start [shape=point];",
+        ];
+
+        let regex = seq(
+            alt(achar('a'), one_of(|ch: char| ch == '"')),
+            star(achar('\\')),
+        );
+        let mut expected = vec!["digraph nfa {"];
+        expected.extend(header);
+        expected.extend([
+            "    start -> 0;",
+            "    9 [shape=doublecircle];",
+            "    0 -> 2 [label=\"ε\"];",
+            "    2 -> 3 [label=\"a\"];",
+            "    3 -> 1 [label=\"ε\"];",
+            "    0 -> 4 [label=\"ε\"];",
+            "    4 -> 5 [label=\"<{{closure}}>\"];",
+            "    5 -> 1 [label=\"ε\"];",
+            "    1 -> 6 [label=\"ε\"];",
+            "    6 -> 7 [label=\"ε\"];",
+            "    7 -> 8 [label=\"\\\\\\\\\"];",
+            "    8 -> 6 [label=\"ε\"];",
+            "    6 -> 9 [label=\"ε\"];",
+            "}",
+        ]);
+        assert_eq!(lines(&nfa(&regex)), expected);
+
+        // Repeats are unrolled.
+        let regex = Pattern::new("x{2,3}").unwrap();
+        assert_eq!(nfa(&regex).matches("label=\"x\"").count(), 3);
+        let regex = exactly(exactly(achar('x'), 1000), 1000);
+        assert!(nfa(&regex).contains("label=\"(?:x{1000}){1000}\""));
+
+        let regex = Pattern::new("(?:a[bc])*").unwrap();
+        let mut expected = vec!["digraph dfa {"];
+        expected.extend(header);
+        expected.extend([
+            "    start -> 0;",
+            "    0 [shape=doublecircle];",
+            "    2 [style=dashed, color=gray];",
+            "    3 [shape=doublecircle];",
+            "    0 -> 1 [label=\"a\"];",
+            "    0 -> 2 [label=\"[b-d]\"];",
+            "    1 -> 2 [label=\"[ad]\"];",
+            "    1 -> 3 [label=\"[bc]\"];",
+            "    3 -> 1 [label=\"a\"];",
+            "    3 -> 2 [label=\"[b-d]\"];",
+            "}",
+        ]);
+        let graph = dfa(regex, &['a', 'b', 'c', 'd']).unwrap();
+        assert_eq!(lines(&graph), expected);
+
+        // The DFA can't be drawn for regexes that use the context.
+        assert!(dfa(seq(word_boundary(), dot()), &['a']).is_none());
+    }
+}
@@ -0,0 +1,231 @@
+use super::prefix::NEXT;
+use super::{syntax, NoSyntaxError, Regex, Syntax};
+use std::collections::HashMap;
+
+// The analyses that look at every state of a regex (`language`, `generate`, `dot::dfa`) explore
+// them with an `Explorer`, and so does the `Dfa` cache, with just the `Interner`: states with
+// equal snapshots (see `Regex.write_state`) are the same state, so each one is only explored
+// once, and this ends if the regex has finitely many, as the built-in combinators do.
+
+/// Ids for states, by their snapshots.
+#[derive(Clone)]
+pub(crate) struct Interner {
+    ids: HashMap<Box<[u8]>, usize>,
+    // The last snapshot looked up.
+    snapshot: Vec<u8>,
+    /// Whether every state looked up so far has had a snapshot.
+    pub(crate) snapshots: bool,
+}
+
+/// The result of `Interner::find`.
+pub(crate) enum Found {
+    /// The state has been seen before, with this id.
+    Old(usize),
+    /// The state is new, and can be given an id with `Interner::insert`.
+    New,
+    /// The state has no snapshot.
+    Unknown,
+}
+
+impl Interner {
+    pub(crate) fn new() -> Interner {
+        Interner {
+            ids: HashMap::new(),
+            snapshot: vec![],
+            snapshots: true,
+        }
+    }
+
+    /// Look up the state whose snapshot `write` writes.
+    pub(crate) fn find(&mut self, write: impl FnOnce(&mut Vec<u8>) -> bool) -> Found {
+        self.snapshot.clear();
+        if !write(&mut self.snapshot) {
+            self.snapshots = false;
+            return Found::Unknown;
+        }
+        match self.ids.get(&self.snapshot[..]) {
+            Some(&id) => Found::Old(id),
+            None => Found::New,
+        }
+    }
+
+    /// Give the state last looked up by `find` the id `id`.
+    pub(crate) fn insert(&mut self, id: usize) {
+        self.ids.insert(self.snapshot.clone().into(), id);
+    }
+}
+
+/// The chars to explore a regex with: one from each range of chars that it treats the same.
+pub(crate) struct Alphabet {
+    /// The ranges, in order.
+    pub(crate) ranges: Vec<(char, char)>,
+    /// A char from each range, preferably one that prints.
+    pub(crate) representatives: Vec<char>,
+}
+
+impl Alphabet {
+    /// The ranges of chars that all of `syntaxes` treat the same. Fails if part of one has no
+    /// syntax, since then any two chars might behave differently.
+    pub(crate) fn new(syntaxes: &[Syntax]) -> Result<Alphabet, NoSyntaxError> {
+        let mut boundaries = vec![];
+        for syntax in syntaxes {
+            syntax.add_boundaries(&mut boundaries)?;
+        }
+        Ok(Alphabet::between(boundaries))
+    }
+
+    /// The ranges between `boundaries` (see `syntax::ranges_between`).
+    pub(crate) fn between(boundaries: Vec<u32>) -> Alphabet {
+        let ranges = syntax::ranges_between(boundaries);
+        let representatives = (ranges.iter())
+            .map(|&(min, max)| (min..=max).find(|ch| !ch.is_control()).unwrap_or(min))
+            .collect();
+        Alphabet {
+            ranges,
+            representatives,
+        }
+    }
+
+    /// Just the given chars, each in a range of its own.
+    pub(crate) fn chars(chars: &[char]) -> Alphabet {
+        Alphabet {
+            ranges: chars.iter().map(|&ch| (ch, ch)).collect(),
+            representatives: chars.to_vec(),
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ranges.len()
+    }
+}
+
+// Regexes that use the context can't take a step until the char after it is known, so as in
+// `Regex.check_prefix`, each state holds the regex before its last step, and the char to take it
+// on. Its snapshot is that of the regex after the step, for each kind of char that could come
+// next (see `prefix::NEXT`), since that is all that its future depends on.
+
+struct State<R> {
+    regex: R,
+    // `None` if the last step is `start()`.
+    last: Option<char>,
+    // The state that each range leads to, or `DEAD` or `UNKNOWN`.
+    children: Vec<usize>,
+}
+
+/// A range that leads to a dead state.
+const DEAD: usize = usize::MAX;
+
+/// A range whose state hasn't been found yet.
+const UNKNOWN: usize = usize::MAX - 1;
+
+impl<R: Regex> State<R> {
+    /// A copy of the regex after its last step, given the char after it.
+    fn stepped(&self, context: bool, next: Option<char>) -> R {
+        let mut regex = self.regex.clone();
+        if context {
+            regex.context(self.last, next);
+        }
+        match self.last {
+            None => regex.start(),
+            Some(ch) => regex.advance(ch),
+        }
+        regex
+    }
+}
+
+/// The states of a regex, explored as they are needed, so that each one only advances over each
+/// range of the alphabet once. Ids are given in the order states are found, from 0 for the first,
+/// so exploring breadth first gives them in breadth first order.
+pub(crate) struct Explorer<R> {
+    context: bool,
+    pub(crate) alphabet: Alphabet,
+    states: Vec<State<R>>,
+    // States without snapshots are all treated as new, so exploring them may not end.
+    interner: Interner,
+}
+
+impl<R: Regex> Explorer<R> {
+    /// Explore from `regex` before its last step, which is `start()` if `last` is `None`, or else
+    /// advancing over `last`.
+    pub(crate) fn new(regex: R, last: Option<char>, alphabet: Alphabet) -> Explorer<R> {
+        let mut explorer = Explorer {
+            context: regex.uses_context(),
+            alphabet,
+            states: vec![],
+            interner: Interner::new(),
+        };
+        explorer.add(regex, last);
+        explorer
+    }
+
+    /// Explore the strings that `regex` matches, from the start.
+    pub(crate) fn start(regex: &R, alphabet: Alphabet) -> Explorer<R> {
+        let mut regex = regex.clone();
+        regex.initialize();
+        Explorer::new(regex, None, alphabet)
+    }
+
+    /// The number of states found so far.
+    pub(crate) fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    /// Have all the states found so far had snapshots?
+    pub(crate) fn has_snapshots(&self) -> bool {
+        self.interner.snapshots
+    }
+
+    /// A copy of the regex of state `id` after its last step, given the char after it.
+    pub(crate) fn stepped(&self, id: usize, next: Option<char>) -> R {
+        self.states[id].stepped(self.context, next)
+    }
+
+    /// Does the regex match at the end of the input, in state `id`?
+    pub(crate) fn accepts(&self, id: usize) -> bool {
+        self.stepped(id, None).accepts()
+    }
+
+    /// The state that a char from range `i` of the alphabet leads to from state `id`, unless it's
+    /// dead.
+    pub(crate) fn child(&mut self, id: usize, i: usize) -> Option<usize> {
+        if self.states[id].children[i] == UNKNOWN {
+            let ch = self.alphabet.representatives[i];
+            let regex = self.stepped(id, Some(ch));
+            self.states[id].children[i] = if regex.is_dead() {
+                DEAD
+            } else {
+                self.add(regex, Some(ch))
+            };
+        }
+        match self.states[id].children[i] {
+            DEAD => None,
+            child => Some(child),
+        }
+    }
+
+    /// The id of the state of `regex` before stepping over `last`, adding it if it's new.
+    fn add(&mut self, regex: R, last: Option<char>) -> usize {
+        self.states.push(State {
+            regex,
+            last,
+            children: vec![UNKNOWN; self.alphabet.len()],
+        });
+        let new = self.states.len() - 1;
+        let (state, context) = (&self.states[new], self.context);
+        let nexts = if context { &NEXT[..] } else { &NEXT[..1] };
+        let found = self
+            .interner
+            .find(|out| (nexts.iter()).all(|&next| state.stepped(context, next).write_state(out)));
+        match found {
+            Found::Old(old) => {
+                self.states.pop();
+                old
+            }
+            Found::New => {
+                self.interner.insert(new);
+                new
+            }
+            Found::Unknown => new,
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use super::boolean::{dedup, write_copies};
+use super::explore::Alphabet;
+use super::{NoSyntaxError, Regex, Syntax};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The fewest insertions, deletions and substitutions of chars that turn `input` into a string
+/// that `regex` matches, if it's at most `max_edits`. See `combinators::fuzzy`. Fails if part of
+/// `regex` has no syntax, since then it isn't known which chars to try inserting.
+pub fn fuzzy_match_cost<R: Regex>(
+    regex: &R,
+    max_edits: usize,
+    input: &str,
+) -> Result<Option<usize>, NoSyntaxError> {
+    let mut fuzzy = Fuzzy::new(regex.clone(), max_edits)?;
+    let context = fuzzy.uses_context();
+    let mut chars = input.chars().peekable();
+    fuzzy.initialize();
+    if context {
+        fuzzy.context(None, chars.peek().copied());
+    }
+    fuzzy.start();
+    while let Some(ch) = chars.next() {
+        if context {
+            fuzzy.context(Some(ch), chars.peek().copied());
+        }
+        fuzzy.advance(ch);
+        if fuzzy.is_dead() {
+            return Ok(None);
+        }
+    }
+    Ok(fuzzy.cost())
+}
+
+// Each tracked string is matched against `regex` in every way of editing it, so like `intersect`,
+// this keeps copies of `regex`: one per edited string, along with how many edits it took. Copies
+// in the same state are merged, keeping the fewest edits, when `regex` supports snapshots.
+//
+// Deleting or substituting a char means advancing a copy over a char that isn't in the input.
+// Rather than try every char, it tries one from each range of chars that `regex.syntax()` treats
+// the same, so `regex` must have syntax.
+
+#[derive(Clone)]
+pub(crate) struct Fuzzy<R: Regex> {
+    // An initialized copy of `regex`, to start new strings with.
+    fresh: R,
+    max_edits: usize,
+    // The ranges of chars that `regex` treats the same, and a char from each.
+    alphabet: Arc<Alphabet>,
+    // Sorted by the number of edits, so that `dedup` keeps the fewest.
+    copies: Vec<(R, usize)>,
+    // Whether a copy was started since the last advance, so another start can be skipped.
+    started: bool,
+    // The char after the current position, for the context of chars that aren't in the input.
+    next: Option<char>,
+}
+
+impl<R: Regex> Fuzzy<R> {
+    pub(crate) fn new(mut regex: R, max_edits: usize) -> Result<Fuzzy<R>, NoSyntaxError> {
+        regex.initialize();
+        Ok(Fuzzy {
+            alphabet: Arc::new(Alphabet::new(&[regex.syntax()])?),
+            fresh: regex,
+            max_edits,
+            copies: vec![],
+            started: false,
+            next: None,
+        })
+    }
+
+    /// The fewest edits of an accepted string, if any.
+    fn cost(&self) -> Option<usize> {
+        self.copies
+            .iter()
+            .filter(|(regex, _)| regex.accepts())
+            .map(|&(_, edits)| edits)
+            .min()
+    }
+
+    /// Advance `regex` over `ch`, which isn't in the input.
+    fn advance_edited(&self, regex: &R, ch: char) -> R {
+        let mut regex = regex.clone();
+        if self.fresh.uses_context() {
+            regex.context(Some(ch), self.next);
+        }
+        regex.advance(ch);
+        regex
+    }
+
+    /// Add the `new` copies, and copies for deleting up to `max_edits` chars from the ends of
+    /// their strings. This goes one number of edits at a time, skipping copies in a state that
+    /// some copy is already in with no more edits, so that each state is only expanded once.
+    fn add(&mut self, new: Vec<(R, usize)>) {
+        let mut levels: Vec<Vec<R>> = vec![vec![]; self.max_edits + 1];
+        for (regex, edits) in new {
+            levels[edits].push(regex);
+        }
+        // The fewest edits of each state so far, by snapshot, if `regex` supports them.
+        let mut fewest = HashMap::new();
+        let mut snapshots = true;
+        for (regex, edits) in &self.copies {
+            let mut snapshot = vec![];
+            snapshots = snapshots && regex.write_state(&mut snapshot);
+            let fewest = fewest.entry(snapshot).or_insert(*edits);
+            *fewest = (*fewest).min(*edits);
+        }
+        for edits in 0..=self.max_edits {
+            for regex in std::mem::take(&mut levels[edits]) {
+                if regex.is_dead() {
+                    continue;
+                }
+                let mut snapshot = vec![];
+                if snapshots && regex.write_state(&mut snapshot) {
+                    if fewest.get(&snapshot).is_some_and(|&fewest| fewest <= edits) {
+                        continue;
+                    }
+                    fewest.insert(snapshot, edits);
+                }
+                if edits < self.max_edits {
+                    for &ch in &self.alphabet.representatives {
+                        levels[edits + 1].push(self.advance_edited(&regex, ch));
+                    }
+                }
+                self.copies.push((regex, edits));
+            }
+        }
+        self.copies.sort_by_key(|&(_, edits)| edits);
+        dedup(&mut self.copies, |(regex, _), out| regex.write_state(out));
+    }
+
+    fn write_copy((regex, edits): &(R, usize), out: &mut Vec<u8>) -> bool {
+        out.extend_from_slice(&(*edits as u32).to_le_bytes());
+        regex.write_state(out)
+    }
+}
+
+impl<R: Regex> Regex for Fuzzy<R> {
+    fn initialize(&mut self) {
+        self.copies.clear();
+        self.started = false;
+    }
+
+    fn start(&mut self) {
+        if !self.started {
+            let mut regex = self.fresh.clone();
+            regex.start();
+            self.add(vec![(regex, 0)]);
+            self.started = true;
+        }
+    }
+
+    fn advance(&mut self, ch: char) {
+        let mut copies = Vec::with_capacity(self.copies.len());
+        for (regex, edits) in std::mem::take(&mut self.copies) {
+            let mut matched = regex.clone();
+            matched.advance(ch);
+            copies.push((matched, edits));
+            if edits < self.max_edits {
+                // Insert `ch`, or substitute a char that `regex` treats differently.
+                let alphabet = &self.alphabet;
+                for (&(min, max), &other) in alphabet.ranges.iter().zip(&alphabet.representatives) {
+                    if !(min..=max).contains(&ch) {
+                        copies.push((self.advance_edited(&regex, other), edits + 1));
+                    }
+                }
+                copies.push((regex, edits + 1));
+            }
+        }
+        self.add(copies);
+        self.started = false;
+    }
+
+    fn accepts(&self) -> bool {
+        self.copies.iter().any(|(regex, _)| regex.accepts())
+    }
+
+    fn is_dead(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.copies.iter().any(|(regex, _)| regex.is_saturated())
+    }
+
+    fn context(&mut self, prev: Option<char>, next: Option<char>) {
+        self.next = next;
+        self.fresh.context(prev, next);
+        for (regex, _) in &mut self.copies {
+            regex.context(prev, next);
+        }
+    }
+
+    fn uses_context(&self) -> bool {
+        self.fresh.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Fuzzy(Box::new(self.fresh.syntax()), self.max_edits)
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        write_copies(&self.copies, Self::write_copy, out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Pattern;
+
+    // The edit distance between `a` and `b`, by the textbook dynamic program.
+    fn distance(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, x) in a.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, &y) in b.iter().enumerate() {
+                let substituted = diagonal + (x != y) as usize;
+                diagonal = row[j + 1];
+                row[j + 1] = substituted.min(row[j] + 1).min(row[j + 1] + 1);
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn fuzzy_matching() {
+        let code = Pattern::new(r"[A-Z]{3}-\d{4}").unwrap();
+        assert_eq!(fuzzy_match_cost(&code, 2, "ABC-1234"), Ok(Some(0)));
+        assert_eq!(fuzzy_match_cost(&code, 2, "ABC1234"), Ok(Some(1)));
+        assert_eq!(fuzzy_match_cost(&code, 2, "A8C-12B4"), Ok(Some(2)));
+        assert_eq!(fuzzy_match_cost(&code, 2, "ABC--1234"), Ok(Some(1)));
+        assert_eq!(fuzzy_match_cost(&code, 2, "AB-12"), Ok(None));
+        assert_eq!(fuzzy_match_cost(&code, 3, "AB-12"), Ok(Some(3)));
+
+        // It composes with other combinators.
+        let mut words = seq(
+            fuzzy(Pattern::new("hello").unwrap(), 1).unwrap(),
+            plus(seq(
+                achar(' '),
+                fuzzy(Pattern::new("world").unwrap(), 1).unwrap(),
+            )),
+        );
+        assert!(words.is_match("hello world"));
+        assert!(words.is_match("helo wrld world"));
+        assert!(words.is_match("hallo worlds"));
+        assert!(!words.is_match("helo wrd"));
+        assert!(words.find("I said: hallo world!").is_some());
+        assert_eq!(
+            fuzzy(empty(), 2).unwrap().syntax().to_string(),
+            "<fuzzy{2}()>"
+        );
+
+        // Any char can be inserted, not just ASCII ones, but then the regex needs syntax.
+        assert_eq!(fuzzy_match_cost(&achar('é'), 1, ""), Ok(Some(1)));
+        assert_eq!(
+            fuzzy_match_cost(&one_of(|ch: char| ch == 'é'), 1, ""),
+            Err(NoSyntaxError {
+                part: "{{closure}}".to_owned()
+            })
+        );
+        assert!(fuzzy(seq(achar('a'), lookahead(achar('b'))), 1).is_err());
+        // Each state is only expanded once per number of edits, so this is quick.
+        let code = Pattern::new(r"\w{3}-[0-9]{4}").unwrap();
+        assert_eq!(fuzzy_match_cost(&code, 3, "AB1-23456"), Ok(Some(1)));
+
+        // The cost is the edit distance to the closest accepted string.
+        let targets = ["", "ab", "ba", "abc", "ca", "aab"];
+        let inner = alt(
+            alt(empty(), Pattern::new("ab|ba|abc").unwrap()),
+            alt(
+                literal_ci("CA"),
+                seq(achar('a'), Pattern::new("ab").unwrap()),
+            ),
+        );
+        for input in ["", "a", "ab", "bb", "cab", "bbbb", "cccc", "abcab", "ÀB"] {
+            let closest = targets.iter().map(|t| distance(input, t)).min().unwrap();
+            for max_edits in 0..3 {
+                let expected = Some(closest).filter(|&d| d <= max_edits);
+                assert_eq!(
+                    fuzzy_match_cost(&inner, max_edits, input),
+                    Ok(expected),
+                    "{:?} with {} edits",
+                    input,
+                    max_edits
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! Generate strings that a regex matches: the reverse of `Regex.is_match(&str)`, e.g. for test
+//! data. `enumerate` lists them in shortlex order, `sample` picks them at random, and `shrink`
+//! finds simpler ones. With the `proptest` feature, `strategy` combines them into a proptest
+//! `Strategy`.
+//!
+//! Like `fuzzy`, these run the regex over one char from each range of chars that its
+//! `Regex.syntax()` treats the same, and count the strings of each length that it matches from each
+//! state, telling states apart by their snapshots (see `Regex.write_state`). So they fail with a
+//! `NoSyntaxError` if part of the regex has no syntax, rather than miss strings, and regexes
+//! without snapshots can take exponential time.
+
+use super::explore::{Alphabet, Explorer};
+use super::{NoSyntaxError, Regex};
+use std::collections::{HashMap, HashSet};
+
+/// Iterate over the strings of at most `max_len` chars that `regex` matches, in shortlex order:
+/// shorter strings first, and strings of the same length in code point order.
+pub fn enumerate<R: Regex>(regex: &R, max_len: usize) -> Result<Enumerate<R>, NoSyntaxError> {
+    Ok(Enumerate {
+        walker: Walker::new(regex)?,
+        max_len,
+        len: 0,
+        stack: vec![],
+        chars: String::new(),
+    })
+}
+
+/// A random string of at most `max_len` chars that `regex` matches, or `None` if there isn't one.
+/// `rng` returns random `u64`s, like `|| rng.next_u64()` with a `rand` RNG.
+///
+/// The length is picked uniformly from those that `regex` has matches of, and then each string of
+/// that length is equally likely (up to floating point error), so long strings aren't favoured
+/// just because there are more of them.
+pub fn sample<R: Regex>(
+    regex: &R,
+    rng: &mut impl FnMut() -> u64,
+    max_len: usize,
+) -> Result<Option<String>, NoSyntaxError> {
+    let mut walker = Walker::new(regex)?;
+    let lens: Vec<usize> = (0..=max_len)
+        .filter(|&len| walker.has_matches(0, len))
+        .collect();
+    if lens.is_empty() {
+        return Ok(None);
+    }
+    let len = lens[(unit(rng()) * lens.len() as f64) as usize % lens.len()];
+
+    let mut id = 0;
+    let mut string = String::with_capacity(len);
+    for left in (0..len).rev() {
+        // Pick a range by how many strings continue through it, then a char from it. The counts
+        // are scaled down by the largest one first, since only their ratios matter.
+        let log_weights: Vec<f64> = (0..walker.ranges().len())
+            .map(|i| match walker.explorer.child(id, i) {
+                Some(child) => walker.size(i).ln() + walker.log_count(child, left),
+                None => f64::NEG_INFINITY,
+            })
+            .collect();
+        let largest = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = log_weights
+            .iter()
+            .map(|log| (log - largest).exp())
+            .collect();
+        let mut target = unit(rng()) * weights.iter().sum::<f64>();
+        let i = (weights.iter())
+            .position(|&weight| {
+                target -= weight;
+                weight > 0.0 && target < 0.0
+            })
+            .unwrap_or_else(|| weights.iter().rposition(|&weight| weight > 0.0).unwrap());
+        let offset = (unit(rng()) * walker.size(i)) as u32;
+        let (min, max) = walker.ranges()[i];
+        string.push(std::char::from_u32(min as u32 + offset).map_or(max, |ch| ch.min(max)));
+        id = walker.explorer.child(id, i).unwrap();
+    }
+    Ok(Some(string))
+}
+
+/// Strings that `regex` matches and that are simpler than `input`: shorter, or as long but earlier
+/// in code point order. They are the simplest string that it matches, then `input` with runs of
+/// chars removed (longest runs first), then `input` with one char replaced by an earlier one.
+pub fn shrink<R: Regex>(regex: &R, input: &str) -> Result<Vec<String>, NoSyntaxError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut regex = regex.clone();
+    let mut candidates: Vec<String> = enumerate(&regex, chars.len())?.take(1).collect();
+    let mut size = chars.len();
+    while size > 0 {
+        for start in 0..=chars.len() - size {
+            let removed: String = (chars[..start].iter())
+                .chain(&chars[start + size..])
+                .collect();
+            if regex.is_match(&removed) {
+                candidates.push(removed);
+            }
+        }
+        size /= 2;
+    }
+    let representatives = Walker::new(&regex)?.explorer.alphabet.representatives;
+    for i in 0..chars.len() {
+        for &ch in representatives.iter().filter(|&&ch| ch < chars[i]) {
+            let mut replaced = chars.clone();
+            replaced[i] = ch;
+            let replaced: String = replaced.into_iter().collect();
+            if regex.is_match(&replaced) {
+                candidates.push(replaced);
+                break;
+            }
+        }
+    }
+    candidates
+        .retain(|candidate| (candidate.chars().count(), &candidate[..]) < (chars.len(), input));
+    let mut seen = HashSet::new();
+    candidates.retain(|candidate| seen.insert(candidate.clone()));
+    Ok(candidates)
+}
+
+/// A float in `[0, 1)` from random bits.
+fn unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// An iterator over the strings that a regex matches, in shortlex order. See `enumerate`.
+pub struct Enumerate<R: Regex> {
+    walker: Walker<R>,
+    max_len: usize,
+    // The length of the strings being listed, plus one.
+    len: usize,
+    // A depth first search through the strings of that length: the state that each char of
+    // `chars` leads to, and the range and code point of the next char to try after it.
+    stack: Vec<(usize, usize, u32)>,
+    chars: String,
+}
+
+impl<R: Regex> Iterator for Enumerate<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            let depth = self.chars.chars().count();
+            let (id, range, next) = match self.stack.last_mut() {
+                Some(&mut (id, ref mut range, ref mut next)) => (id, range, next),
+                None => {
+                    if self.len > self.max_len {
+                        return None;
+                    }
+                    if self.walker.has_matches(0, self.len) {
+                        self.stack.push((0, 0, 0));
+                    }
+                    self.len += 1;
+                    continue;
+                }
+            };
+            let left = self.len - 1 - depth;
+            if left == 0 {
+                let string = self.chars.clone();
+                self.stack.pop();
+                self.chars.pop();
+                return Some(string);
+            }
+            // Find the next char that leads to a match, skipping ranges that don't.
+            let mut child = None;
+            while let Some(&(min, max)) = self.walker.ranges().get(*range) {
+                let alive = match self.walker.explorer.child(id, *range) {
+                    Some(child) => self.walker.has_matches(child, left - 1),
+                    None => false,
+                };
+                *next = (*next).max(min as u32);
+                if !alive || *next > max as u32 {
+                    *range += 1;
+                    *next = 0;
+                    continue;
+                }
+                child = (self.walker.explorer.child(id, *range)).map(|child| (*next, child));
+                *next += 1;
+                break;
+            }
+            match child {
+                Some((ch, child)) => {
+                    self.stack.push((child, 0, 0));
+                    self.chars.push(std::char::from_u32(ch).unwrap());
+                }
+                None => {
+                    self.stack.pop();
+                    self.chars.pop();
+                }
+            }
+        }
+    }
+}
+
+/**********/
+/* Walker */
+/**********/
+
+// The states of the regex (see `explore`), and how many strings lead from each one to a match.
+// The counts are kept as logs, since they overflow even an `f64` for long strings over wide
+// ranges, like 60 chars of `.`.
+
+struct Walker<R> {
+    explorer: Explorer<R>,
+    // The log of how many strings of each length lead from each state to a match.
+    log_counts: HashMap<(usize, usize), f64>,
+}
+
+impl<R: Regex> Walker<R> {
+    fn new(regex: &R) -> Result<Walker<R>, NoSyntaxError> {
+        Ok(Walker {
+            explorer: Explorer::start(regex, Alphabet::new(&[regex.syntax()])?),
+            log_counts: HashMap::new(),
+        })
+    }
+
+    fn ranges(&self) -> &[(char, char)] {
+        &self.explorer.alphabet.ranges
+    }
+
+    /// The number of chars in range `i`.
+    fn size(&self, i: usize) -> f64 {
+        let (min, max) = self.ranges()[i];
+        (max as u32 - min as u32 + 1) as f64
+    }
+
+    /// The log of how many strings of `len` chars lead from state `id` to a match, or `-inf` if
+    /// none do.
+    fn log_count(&mut self, id: usize, len: usize) -> f64 {
+        if len == 0 {
+            return if self.explorer.accepts(id) {
+                0.0
+            } else {
+                f64::NEG_INFINITY
+            };
+        }
+        if let Some(&log_count) = self.log_counts.get(&(id, len)) {
+            return log_count;
+        }
+        let logs: Vec<f64> = (0..self.ranges().len())
+            .filter_map(|i| {
+                let child = self.explorer.child(id, i)?;
+                Some(self.size(i).ln() + self.log_count(child, len - 1))
+            })
+            .collect();
+        // Add up the counts relative to the largest one, so that none of them overflow.
+        let largest = logs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let log_count = if largest == f64::NEG_INFINITY {
+            largest
+        } else {
+            largest
+                + logs
+                    .iter()
+                    .map(|log| (log - largest).exp())
+                    .sum::<f64>()
+                    .ln()
+        };
+        self.log_counts.insert((id, len), log_count);
+        log_count
+    }
+
+    /// Does any string of `len` chars lead from state `id` to a match?
+    fn has_matches(&mut self, id: usize, len: usize) -> bool {
+        self.log_count(id, len) > f64::NEG_INFINITY
+    }
+}
+
+/************/
+/* Proptest */
+/************/
+
+#[cfg(feature = "proptest")]
+pub use self::strategy::{strategy, RegexStrategy, Shrinker};
+
+#[cfg(feature = "proptest")]
+mod strategy {
+    use super::{sample, shrink};
+    use crate::Regex;
+    use proptest::prelude::Rng;
+    use proptest::strategy::{NewTree, Strategy, ValueTree};
+    use proptest::test_runner::TestRunner;
+    use std::fmt;
+
+    /// A proptest `Strategy` for strings of at most `max_len` chars that `regex` matches, picked by
+    /// `sample` and shrunk by `shrink`. Generating values fails if part of `regex` has no syntax.
+    pub fn strategy<R: Regex>(regex: R, max_len: usize) -> RegexStrategy<R> {
+        RegexStrategy { regex, max_len }
+    }
+
+    /// See `strategy`.
+    #[derive(Clone)]
+    pub struct RegexStrategy<R: Regex> {
+        regex: R,
+        max_len: usize,
+    }
+
+    impl<R: Regex> fmt::Debug for RegexStrategy<R> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "strategy({}, {})", self.regex.syntax(), self.max_len)
+        }
+    }
+
+    impl<R: Regex> Strategy for RegexStrategy<R> {
+        type Tree = Shrinker<R>;
+        type Value = String;
+
+        fn new_tree(&self, runner: &mut TestRunner) -> NewTree<Self> {
+            let rng = runner.rng();
+            match sample(&self.regex, &mut || rng.next_u64(), self.max_len) {
+                Ok(Some(string)) => Ok(Shrinker::new(self.regex.clone(), string)),
+                Ok(None) => Err(format!("{} matches no strings", self.regex.syntax()).into()),
+                Err(err) => Err(err.to_string().into()),
+            }
+        }
+    }
+
+    // Shrinkers are only made for strings that `sample` found, so the regex has syntax.
+    const SAMPLED: &str = "sampled regexes have syntax";
+
+    /// A proptest `ValueTree` that shrinks a matching string with `shrink`.
+    pub struct Shrinker<R: Regex> {
+        regex: R,
+        current: String,
+        // The candidates to simplify `current` to, and the next one to try.
+        candidates: Vec<String>,
+        next: usize,
+        // The state before the last simplification, to go back to if it went too far.
+        previous: Option<(String, Vec<String>, usize)>,
+    }
+
+    impl<R: Regex> Shrinker<R> {
+        fn new(regex: R, current: String) -> Shrinker<R> {
+            Shrinker {
+                candidates: shrink(&regex, &current).expect(SAMPLED),
+                regex,
+                current,
+                next: 0,
+                previous: None,
+            }
+        }
+    }
+
+    impl<R: Regex> ValueTree for Shrinker<R> {
+        type Value = String;
+
+        fn current(&self) -> String {
+            self.current.clone()
+        }
+
+        fn simplify(&mut self) -> bool {
+            let candidate = match self.candidates.get(self.next) {
+                Some(candidate) => candidate.clone(),
+                None => return false,
+            };
+            self.next += 1;
+            let candidates = shrink(&self.regex, &candidate).expect(SAMPLED);
+            let current = std::mem::replace(&mut self.current, candidate);
+            let candidates = std::mem::replace(&mut self.candidates, candidates);
+            self.previous = Some((current, candidates, self.next));
+            self.next = 0;
+            true
+        }
+
+        fn complicate(&mut self) -> bool {
+            match self.previous.take() {
+                Some((current, candidates, next)) => {
+                    self.current = current;
+                    self.candidates = candidates;
+                    self.next = next;
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Pattern;
+
+    #[test]
+    fn generation() {
+        let mut bin = Pattern::new("0|1[01]*").unwrap();
+        let strings: Vec<String> = enumerate(&bin, 3).unwrap().collect();
+        assert_eq!(strings, ["0", "1", "10", "11", "100", "101", "110", "111"]);
+        assert_eq!(enumerate(&achar('x'), 0).unwrap().count(), 0);
+        assert_eq!(
+            enumerate(&star(achar('x')), 2).unwrap().collect::<Vec<_>>(),
+            ["", "x", "xx"]
+        );
+        assert_eq!(
+            enumerate(&Pattern::new(r"(?i)k\b").unwrap(), 5)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            ["K", "k", "\u{212A}"]
+        );
+        // Ranges are listed char by char, lazily.
+        let mut any = enumerate(&seq(dot(), achar('!')), 2).unwrap();
+        assert_eq!(any.next().as_deref(), Some("\0!"));
+        assert_eq!(any.nth(99).as_deref(), Some("d!"));
+
+        // A fixed "random" sequence, so the test is repeatable.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut rng = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut lens = [0; 4];
+        for _ in 0..200 {
+            let string = sample(&bin, &mut rng, 3).unwrap().unwrap();
+            assert!(bin.is_match(&string), "{:?}", string);
+            lens[string.len()] += 1;
+        }
+        assert!(lens[1..].iter().all(|&count| count > 40), "{:?}", lens);
+        let mut word = Pattern::new(r"\w+@[a-z]{2,3}\.\p{Greek}").unwrap();
+        for _ in 0..5 {
+            let string = sample(&word, &mut rng, 8).unwrap().unwrap();
+            assert!(word.is_match(&string), "{:?}", string);
+        }
+        assert_eq!(
+            sample(&Pattern::new("a{4}").unwrap(), &mut rng, 3),
+            Ok(None)
+        );
+        // There are too many long strings to count in an `f64`, but both halves of the alphabet
+        // should still turn up at the start of them.
+        let mut halves = Pattern::new("(?:[a-m]|[n-z])*").unwrap();
+        let mut long = 0;
+        while long < 3 {
+            let string = sample(&halves, &mut rng, 300).unwrap().unwrap();
+            assert!(halves.is_match(&string), "{:?}", string);
+            if string.len() > 250 {
+                let start = &string[..50];
+                assert!(start.contains(|ch| ch <= 'm') && start.contains(|ch| ch >= 'n'));
+                long += 1;
+            }
+        }
+
+        let shrunk = shrink(&bin, "1101").unwrap();
+        assert_eq!(shrunk[0], "0");
+        assert!(shrunk.contains(&"110".to_owned()));
+        assert!(shrunk.contains(&"1001".to_owned()));
+        for string in shrunk {
+            assert!(bin.is_match(&string));
+        }
+        assert_eq!(shrink(&bin, "0"), Ok(vec![]));
+
+        // Parts without syntax could tell any chars apart, so no strings can be ruled out.
+        let accent = seq(one_of(|ch: char| ch == 'é'), achar('x'));
+        let err = NoSyntaxError {
+            part: "{{closure}}".to_owned(),
+        };
+        assert_eq!(enumerate(&accent, 2).err(), Some(err.clone()));
+        assert_eq!(sample(&accent, &mut rng, 2), Err(err.clone()));
+        assert_eq!(shrink(&accent, "éx"), Err(err));
+    }
+
+    #[cfg(feature = "proptest")]
+    #[test]
+    fn proptest_strategy() {
+        use proptest::test_runner::{TestError, TestRunner};
+
+        let mut runner = TestRunner::deterministic();
+        let id = Pattern::new(r"[a-e]+-\d{2}").unwrap();
+        let ids = strategy(id.clone(), 8);
+        let result = runner.run(&ids, |string| {
+            proptest::prop_assert!(id.clone().is_match(&string));
+            Ok(())
+        });
+        assert!(result.is_ok());
+        // A failing case shrinks to the simplest string that still fails.
+        let result = TestRunner::deterministic().run(&ids, |string| {
+            proptest::prop_assert!(!string.contains('c'));
+            Ok(())
+        });
+        match result {
+            Err(TestError::Fail(_, string)) => assert_eq!(string, "c-00"),
+            other => panic!("{:?}", other),
+        }
+    }
+}
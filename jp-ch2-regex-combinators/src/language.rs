@@ -0,0 +1,158 @@
+use super::boolean::{Complement, Intersect};
+use super::explore::{Alphabet, Explorer};
+use super::{Alt, Regex, Syntax};
+
+/// The outcome of `is_empty_language`, `is_subset` or `equivalent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The property holds.
+    Holds,
+    /// The property doesn't hold, as this string shows. It is one of the shortest that do.
+    Counterexample(String),
+    /// The property can't be checked, because part of a regex has no syntax, so which chars it
+    /// tells apart is unknown (see `Regex.syntax()`), or doesn't support snapshots, so its states
+    /// can't be told apart (see `Regex.write_state`).
+    Unknown,
+}
+
+/// Does `regex` match no strings at all? If not, the counterexample is a string that it matches.
+pub fn is_empty_language<R: Regex>(regex: &R) -> Verdict {
+    explore(regex.clone(), &[regex.syntax()])
+}
+
+/// Does `b` match every string that `a` matches? If not, the counterexample is a string that `a`
+/// matches but `b` doesn't.
+pub fn is_subset<P: Regex, Q: Regex>(a: &P, b: &Q) -> Verdict {
+    let difference = Intersect::new(a.clone(), Complement::new(b.clone()));
+    explore(difference, &[a.syntax(), b.syntax()])
+}
+
+/// Do `a` and `b` match the same strings? If not, the counterexample is a string that one of them
+/// matches and the other doesn't.
+pub fn equivalent<P: Regex, Q: Regex>(a: &P, b: &Q) -> Verdict {
+    let difference = Alt(
+        Intersect::new(a.clone(), Complement::new(b.clone())),
+        Intersect::new(b.clone(), Complement::new(a.clone())),
+    );
+    explore(difference, &[a.syntax(), b.syntax()])
+}
+
+// Each analysis looks for a string that some regex matches, by exploring its states breadth
+// first (see `explore`), so that the string found is one of the shortest.
+
+fn explore<R: Regex>(regex: R, syntaxes: &[Syntax]) -> Verdict {
+    let alphabet = match Alphabet::new(syntaxes) {
+        Ok(alphabet) => alphabet,
+        Err(_) => return Verdict::Unknown,
+    };
+    let mut explorer = Explorer::start(&regex, alphabet);
+    // The state that each state was first reached from, and the range of the char it was reached
+    // by (`None` for the start). States are found breadth first, so this is in order of their ids.
+    let mut parents = vec![None];
+    let mut id = 0;
+    while id < parents.len() {
+        if !explorer.has_snapshots() {
+            return Verdict::Unknown;
+        }
+        if explorer.accepts(id) {
+            return Verdict::Counterexample(path(&explorer, &parents, id));
+        }
+        for i in 0..explorer.alphabet.len() {
+            if explorer.child(id, i) == Some(parents.len()) {
+                parents.push(Some((id, i)));
+            }
+        }
+        id += 1;
+    }
+    Verdict::Holds
+}
+
+/// The string that leads to state `id`.
+fn path<R>(explorer: &Explorer<R>, parents: &[Option<(usize, usize)>], mut id: usize) -> String {
+    let mut chars = vec![];
+    while let Some((parent, i)) = parents[id] {
+        chars.push(explorer.alphabet.representatives[i]);
+        id = parent;
+    }
+    chars.iter().rev().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Pattern;
+    use Verdict::*;
+
+    fn pattern(pattern: &str) -> Pattern {
+        Pattern::new(pattern).unwrap()
+    }
+
+    #[test]
+    fn languages() {
+        assert_eq!(
+            is_empty_language(&pattern("[a-c]+d")),
+            Counterexample("ad".into())
+        );
+        assert_eq!(is_empty_language(&pattern(r"a\B")), Holds);
+
+        let ab = star(alt(achar('a'), achar('b')));
+        assert_eq!(equivalent(&ab, &pattern("[ab]*")), Holds);
+        assert_eq!(equivalent(&ab, &pattern("(a*b*)*")), Holds);
+        assert_eq!(
+            equivalent(&ab, &pattern("(ab)*")),
+            Counterexample("a".into())
+        );
+        assert_eq!(
+            equivalent(&pattern("a*"), &pattern("a+")),
+            Counterexample("".into())
+        );
+        assert_eq!(
+            equivalent(&literal_ci("k"), &pattern("[kK\u{212A}]")),
+            Holds
+        );
+        assert_eq!(equivalent(&pattern(r"\bfoo\b"), &pattern("foo")), Holds);
+        assert_eq!(equivalent(&pattern(r"(?m)a$\n?"), &pattern("a\n?")), Holds);
+
+        assert_eq!(is_subset(&pattern("[0-9]{3}"), &pattern(r"\d+")), Holds);
+        assert_eq!(
+            is_subset(&pattern(r"\d+"), &pattern("[0-9]{3}")),
+            Counterexample("0".into())
+        );
+        assert_eq!(
+            is_subset(&pattern("x[a-z]{2,}"), &pattern("x[a-y]*(z|zz)?")),
+            Counterexample("xza".into())
+        );
+        // Closures have no syntax to take the alphabet from.
+        let digit = one_of(|ch: char| ch.is_ascii_digit());
+        assert_eq!(is_subset(&digit, &pattern("[0-9]")), Unknown);
+        assert_eq!(is_empty_language(&intersect(achar('a'), achar('b'))), Holds);
+        let not_ab = intersect(pattern("[a-c]"), complement(pattern("a|b")));
+        assert_eq!(equivalent(&not_ab, &achar('c')), Holds);
+        assert_eq!(
+            is_empty_language(&fuzzy(achar('a'), 1).unwrap()),
+            Counterexample("".into())
+        );
+
+        // Counterexamples really are in one language and not the other.
+        let patterns = [
+            "",
+            "a|b",
+            "(a|ab)(c|bcd)",
+            "abcd|abc",
+            "[a-c]*c",
+            r"(?i)K\b",
+            "k",
+        ];
+        for a in &patterns {
+            for b in &patterns {
+                let (mut a, mut b) = (pattern(a), pattern(b));
+                match equivalent(&a, &b) {
+                    Holds => assert_eq!(a.to_string(), b.to_string()),
+                    Counterexample(input) => assert_ne!(a.is_match(&input), b.is_match(&input)),
+                    Unknown => panic!("{} and {} are unknown", a, b),
+                }
+            }
+        }
+    }
+}
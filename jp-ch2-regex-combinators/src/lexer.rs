@@ -0,0 +1,220 @@
+use super::{BoxedRegex, Pos, Regex, Span};
+use std::error::Error;
+use std::fmt;
+
+/// A tokenizer, built from a list of rules that each give a kind of token and a regex for it.
+///
+/// At each position, the longest prefix of the rest of the input that any rule matches becomes
+/// the next token ("maximal munch"), and if several rules match it, the one added first wins. So
+/// keywords should be added before identifiers. Empty matches are ignored, so every token is at
+/// least one char long.
+#[derive(Clone)]
+pub struct Lexer<K> {
+    rules: Vec<(K, BoxedRegex)>,
+    // Which rules use the context, so that the others can skip it.
+    context: Vec<bool>,
+}
+
+impl<K: Clone> Lexer<K> {
+    /// A lexer with no rules, which rejects any non-empty input.
+    pub fn new() -> Lexer<K> {
+        Lexer {
+            rules: vec![],
+            context: vec![],
+        }
+    }
+
+    /// Add a rule, producing tokens of kind `kind` for strings that `regex` matches. It has lower
+    /// priority than the rules added before it.
+    pub fn rule(mut self, kind: K, regex: impl Regex + Send + Sync + 'static) -> Lexer<K> {
+        self.context.push(regex.uses_context());
+        self.rules.push((kind, BoxedRegex::new(regex)));
+        self
+    }
+
+    /// Iterate over the tokens of `input`, with their positions. If no rule matches at some
+    /// position, the iterator produces an error and stops.
+    pub fn tokenize<'l, 't>(&'l mut self, input: &'t str) -> Tokens<'l, 't, K> {
+        Tokens {
+            lexer: self,
+            input,
+            pos: Pos::default(),
+            failed: false,
+        }
+    }
+
+    /// The rule that matches the longest non-empty prefix of `input[start..]`, and where its
+    /// match ends.
+    fn longest_match(&mut self, input: &str, start: Pos) -> Option<(usize, Pos)> {
+        let Lexer { rules, context } = self;
+        let mut chars = input[start.byte..].chars().peekable();
+        let prev = input[..start.byte].chars().next_back();
+        let next = chars.peek().copied();
+        let mut live = vec![];
+        for (i, (_, regex)) in rules.iter_mut().enumerate() {
+            regex.initialize();
+            if context[i] {
+                regex.context(prev, next);
+            }
+            regex.start();
+            if !regex.is_dead() {
+                live.push(i);
+            }
+        }
+        let mut best = None;
+        let mut pos = start;
+        while let Some(ch) = chars.next() {
+            if live.is_empty() {
+                break;
+            }
+            pos = pos.after(ch);
+            let next = chars.peek().copied();
+            for &i in &live {
+                let regex = &mut rules[i].1;
+                if context[i] {
+                    regex.context(Some(ch), next);
+                }
+                regex.advance(ch);
+            }
+            // `live` is in priority order.
+            if let Some(&i) = live.iter().find(|&&i| rules[i].1.accepts()) {
+                best = Some((i, pos));
+            }
+            live.retain(|&i| !rules[i].1.is_dead());
+        }
+        best
+    }
+}
+
+impl<K: Clone> Default for Lexer<K> {
+    fn default() -> Lexer<K> {
+        Lexer::new()
+    }
+}
+
+/// An iterator over the tokens of a string, and where they are. See `Lexer.tokenize(&str)`.
+pub struct Tokens<'l, 't, K> {
+    lexer: &'l mut Lexer<K>,
+    input: &'t str,
+    pos: Pos,
+    failed: bool,
+}
+
+impl<'l, 't, K: Clone> Iterator for Tokens<'l, 't, K> {
+    type Item = Result<(K, Span), LexError>;
+
+    fn next(&mut self) -> Option<Result<(K, Span), LexError>> {
+        if self.failed || self.pos.byte == self.input.len() {
+            return None;
+        }
+        let start = self.pos;
+        match self.lexer.longest_match(self.input, start) {
+            Some((rule, end)) => {
+                self.pos = end;
+                let kind = self.lexer.rules[rule].0.clone();
+                Some(Ok((kind, Span { start, end })))
+            }
+            None => {
+                self.failed = true;
+                Some(Err(LexError { pos: start }))
+            }
+        }
+    }
+}
+
+/// An error from tokenizing input that no rule matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexError {
+    /// Where in the input the unmatched text starts.
+    pub pos: Pos,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "no token matches at byte {}", self.pos.byte)
+    }
+}
+
+impl Error for LexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Pattern;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Kind {
+        If,
+        Ident,
+        Number,
+        Le,
+        Lt,
+        Space,
+    }
+
+    #[test]
+    fn lexer() {
+        use Kind::*;
+
+        let pattern = |pattern| Pattern::new(pattern).unwrap();
+        let mut lexer = Lexer::new()
+            .rule(If, pattern(r"if\b"))
+            .rule(Ident, pattern(r"[a-zα-ω_][a-zα-ω_0-9]*"))
+            .rule(Number, pattern(r"[0-9]+(\.[0-9]+)?"))
+            .rule(Le, literal_ci("<="))
+            .rule(Lt, achar('<'))
+            .rule(Space, plus(achar(' ')));
+        let tokens = |lexer: &mut Lexer<Kind>, input| {
+            lexer
+                .tokenize(input)
+                .map(|token| token.map(|(kind, span)| (kind, span.bytes())))
+                .collect::<Vec<_>>()
+        };
+
+        // Keywords beat identifiers of the same length, but not longer ones.
+        assert_eq!(
+            tokens(&mut lexer, "if iffy<=3.5<x"),
+            [
+                Ok((If, 0..2)),
+                Ok((Space, 2..3)),
+                Ok((Ident, 3..7)),
+                Ok((Le, 7..9)),
+                Ok((Number, 9..12)),
+                Ok((Lt, 12..13)),
+                Ok((Ident, 13..14)),
+            ]
+        );
+        assert_eq!(tokens(&mut lexer, "if_"), [Ok((Ident, 0..3))]);
+        // `3.` isn't a number, so the longest match is `3`, and then nothing matches `.`.
+        assert_eq!(
+            tokens(&mut lexer, "αβ 3.x"),
+            [
+                Ok((Ident, 0..4)),
+                Ok((Space, 4..5)),
+                Ok((Number, 5..6)),
+                Err(LexError {
+                    pos: Pos { byte: 6, char: 4 }
+                }),
+            ]
+        );
+        assert_eq!(
+            LexError {
+                pos: Pos { byte: 6, char: 4 }
+            }
+            .to_string(),
+            "no token matches at byte 6"
+        );
+        assert!(tokens(&mut lexer, "").is_empty());
+
+        // Rules that match the empty string don't produce empty tokens.
+        let mut lexer = Lexer::new()
+            .rule(Space, star(achar(' ')))
+            .rule(Ident, plus(char_range('a', 'z')));
+        assert_eq!(
+            tokens(&mut lexer, "ab  c"),
+            [Ok((Ident, 0..2)), Ok((Space, 2..4)), Ok((Ident, 4..5))]
+        );
+        assert_eq!(tokens(&mut Lexer::new(), "a").len(), 1);
+    }
+}
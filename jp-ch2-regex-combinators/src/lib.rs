@@ -1,11 +1,61 @@
 #![feature(test)]
 extern crate test;
 
+mod assertions;
+mod boolean;
+mod boxed;
+mod captures;
+mod case;
+mod dfa;
+#[cfg(test)]
+mod differential;
+mod explore;
+mod fuzzy;
+mod language;
+mod lexer;
+mod parse;
+mod prefix;
+mod replace;
+mod search;
+mod set;
+mod stream;
+mod syntax;
+
+pub mod bytes;
+pub mod dot;
+pub mod generate;
+pub mod predicates;
+pub mod weighted;
+
+pub use assertions::Anchor;
+pub use boxed::{BoxedRegex, DynRegex};
+pub use captures::{Captures, Pos, Span, Tags};
+pub use dfa::{compile_dfa, Dfa};
+pub use fuzzy::fuzzy_match_cost;
+pub use language::{equivalent, is_empty_language, is_subset, Verdict};
+pub use lexer::{LexError, Lexer, Tokens};
+pub use parse::{ParseError, ParseErrorKind, Pattern};
+pub use prefix::PrefixStatus;
+pub use replace::{NoExpand, Replacer};
+pub use search::{FindIter, MatchKind, OverlappingIter, Split, SplitN};
+pub use set::RegexSet;
+pub use stream::{Matcher, Utf8Error};
+pub use syntax::{NoSyntaxError, Syntax};
+
+use assertions::Assert;
+use captures::Group;
+use predicates::CharSet;
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::ops::Range;
+
 /// A trait for Regex combinators. The key to combinators is a shared interface. This interface
 /// allows for `O(NM)` regex parsing.
 ///
-/// Users only need call the `Regex.is_match(&str)` method. (And that is all that is supported:
-/// there are no match groups.)
+/// Users only need call the `Regex.is_match(&str)` method, or `Regex.captures(&str)` to find out
+/// where each `group` matched, or `Regex.find(&str)` to search for the regex inside a larger
+/// string. To match input that arrives a chunk at a time, use a `Matcher`, to match many regexes
+/// at once, use a `RegexSet`, and to split input into tokens, use a `Lexer`.
 ///
 /// # Spec
 ///
@@ -20,26 +70,133 @@ extern crate test;
 ///
 /// **Requirement.** The `accepts()` method returns true iff the `Regex` accepts any of the strings
 /// in its tracking set.
-pub trait Regex: Clone {
+///
+/// # Tagged Spec
+///
+/// To report match groups, each tracked string can also carry `Tags`, saying where it started and
+/// where the groups it passed through matched. The `_tagged` methods are the same as their
+/// untagged counterparts, except that:
+///
+/// - `start_tagged(tags, pos)` tags the new empty string with `tags`.
+/// - `advance_tagged(ch, pos)` keeps each string's tags as it appends `ch` to it.
+/// - `accepted_tags(pos)` returns the tags of the highest priority accepted string (see
+///   `Tags::prefer`), or `None` if `accepts()` is false.
+///
+/// The `pos` argument is the current position in the input: after `ch`, in the case of
+/// `advance_tagged`. The default implementations just call the untagged methods, so combinators
+/// that don't override them still match correctly, but lose the tags of the strings they track.
+///
+/// # State Snapshots
+///
+/// `write_state(out)` appends an encoding of the (untagged) state to `out`, so that states can be
+/// hashed and compared, e.g. by `compile_dfa`. It must obey:
+///
+/// - Two regexes built the same way whose encodings are equal behave the same from then on.
+/// - Encodings are self-delimiting: one is never a proper prefix of another, so that combinators
+///   can concatenate the encodings of their parts. (Write a length before variable-length data.)
+///
+/// The default implementation returns `false`, meaning that the regex can't take snapshots, and
+/// so neither can any combinator containing it.
+///
+/// # Context
+///
+/// Zero-width assertions like `word_boundary()` need to see the chars around the position in the
+/// input that the tracked strings end at. If `uses_context()` is true, then before each `start()`
+/// and each `advance(ch)`, `context(prev, next)` is called with the chars just before and after
+/// the position the strings will end at afterwards (so for `advance(ch)`, `prev` is `ch`). Either
+/// is `None` at the start or end of the input. Combinators must pass the context on to all of
+/// their parts, including ones that aren't tracking any strings yet.
+///
+/// # Symbols
+///
+/// Regexes match sequences of chars by default, but the core combinators (`seq`, `alt`, `star`,
+/// `one_of`, etc.) work over any `Symbol` type `T`, like tokens or events, as a `Regex<T>`. Use
+/// `is_match_symbols` to match a sequence of them. The methods that take a `&str`, and the
+/// combinators that are about text (like `achar_ci` or `word_boundary`), only apply to chars.
+pub trait Regex<T: Symbol = char>: Clone {
     /// Reset to the initial, _empty_ state. In NFA terms, this is an empty set of states.
     fn initialize(&mut self);
     /// Track an empty string.
     fn start(&mut self);
     /// Append `ch` to every string being tracked.
-    fn advance(&mut self, ch: char);
+    fn advance(&mut self, ch: T);
     /// Does the regex match any of the tracked strings?
     fn accepts(&self) -> bool;
     /// Is it true that both (i) accepts() is false, and (ii) accepts() will remain false for any
     /// possible sequence of `advance`s? This is used for a short-circuiting optimization.
     fn is_dead(&self) -> bool;
+    /// Is it true that both (i) accepts() is true, and (ii) accepts() will remain true for any
+    /// possible sequence of `advance`s? This is the opposite of `is_dead`, and like it, is only
+    /// used for optimizations (and by `complement`). It is always safe to return false.
+    fn is_saturated(&self) -> bool {
+        false
+    }
+    /// Does this regex match every string of exactly one char, like `dot()` does? This doesn't
+    /// depend on the state; it lets combinators like `star` tell when they are saturated. It is
+    /// always safe to return false.
+    fn matches_every_char(&self) -> bool {
+        false
+    }
+    /// Tell the regex which chars surround the position its strings will end at after the next
+    /// `start` or `advance`. See "Context" above.
+    fn context(&mut self, _prev: Option<T>, _next: Option<T>) {}
+    /// Does this regex need to be told the context? Like `matches_every_char`, this doesn't depend
+    /// on the state. If it's false, drivers can skip calling `context`.
+    fn uses_context(&self) -> bool {
+        false
+    }
+    /// Describe how this regex was built, to print it as a pattern (with `{}`) or as a tree (with
+    /// `{:?}`). Like `matches_every_char`, this doesn't depend on the state. The default describes
+    /// the regex by the name of its type.
+    fn syntax(&self) -> Syntax {
+        Syntax::Opaque(syntax::type_name::<Self>())
+    }
+
+    /// Track an empty string, tagged with `tags`.
+    fn start_tagged(&mut self, _tags: &Tags, _pos: Pos) {
+        self.start();
+    }
+    /// Append `ch` to every string being tracked, keeping their tags.
+    fn advance_tagged(&mut self, ch: T, _pos: Pos) {
+        self.advance(ch);
+    }
+    /// The tags of the highest priority accepted string, if any.
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        if self.accepts() {
+            Some(Tags::default())
+        } else {
+            None
+        }
+    }
+
+    /// Append an encoding of the current state to `out`, or return `false` if this regex doesn't
+    /// support snapshots. See "State Snapshots" above.
+    fn write_state(&self, _out: &mut Vec<u8>) -> bool {
+        false
+    }
 
     /// Does the input match this regex? Note that this is not looking for an occurrence of the
     /// Regex pattern _somewhere_ in the input; it's specifically checking that the _entire input_
     /// matches the regex.
-    fn is_match(&mut self, input: &str) -> bool {
+    fn is_match(&mut self, input: &str) -> bool
+    where
+        Self: Regex<char>,
+    {
+        <Self as Regex<char>>::is_match_symbols(self, input.chars())
+    }
+
+    /// Like `is_match`, for a sequence of symbols of any type, like tokens. (A `&str` isn't a
+    /// sequence of chars as far as `IntoIterator` is concerned, hence the separate method.)
+    fn is_match_symbols(&mut self, input: impl IntoIterator<Item = T>) -> bool {
+        if self.uses_context() {
+            return search::is_match_in_context(self, input);
+        }
         self.initialize();
         self.start();
-        for ch in input.chars() {
+        for ch in input {
+            if self.is_saturated() {
+                return true;
+            }
             self.advance(ch);
             if self.is_dead() {
                 return false;
@@ -47,34 +204,191 @@ pub trait Regex: Clone {
         }
         self.accepts()
     }
+
+    /// Could `input` be the start of a match? `Complete` if it matches, `Incomplete` if it doesn't
+    /// but some longer input starting with it does, and `Impossible` otherwise. E.g. for checking
+    /// a form field while it's being typed.
+    fn check_prefix(&mut self, input: &str) -> PrefixStatus
+    where
+        Self: Regex<char>,
+    {
+        prefix::check_prefix(self, input)
+    }
+
+    /// The chars that could come next after `input`: those for which `check_prefix` of `input`
+    /// followed by the char isn't `Impossible`. This tries one char from each range of chars that
+    /// the regex's `syntax()` treats the same, so it fails if part of the regex has no syntax.
+    fn next_chars(&mut self, input: &str) -> Result<CharSet, NoSyntaxError>
+    where
+        Self: Regex<char>,
+    {
+        prefix::next_chars(self, input)
+    }
+
+    /// Like `is_match`, but if the input matches, also report where each `group` in the regex
+    /// matched. If a group matched more than once (e.g. inside a `star`), its last match is
+    /// reported. This takes `O(NMG)` time, where `G` is the number of groups.
+    fn captures(&mut self, input: &str) -> Option<Captures>
+    where
+        Self: Regex<char>,
+    {
+        captures::captures(self, input)
+    }
+
+    /// Find the leftmost-longest occurrence of this regex _somewhere_ in the input, and return its
+    /// byte range. This takes `O(NMG)` time, like `captures`.
+    ///
+    /// Combinators that don't implement the tagged methods can't say where their matches started,
+    /// so the reported start of matches that pass through them is not reliable.
+    fn find(&mut self, input: &str) -> Option<Range<usize>>
+    where
+        Self: Regex<char>,
+    {
+        search::search(self, input, 0, MatchKind::LeftmostLongest, false)
+            .map(|caps| caps.span().bytes())
+    }
+
+    /// Iterate over the non-overlapping leftmost-longest occurrences of this regex in the input.
+    /// Empty matches are handled the same way as in the `regex` crate.
+    fn find_iter<'r, 't>(&'r mut self, input: &'t str) -> FindIter<'r, 't, Self>
+    where
+        Self: Regex<char>,
+    {
+        FindIter::new(self, input)
+    }
+
+    /// Iterate over the longest match starting at each position in the input, including matches
+    /// that overlap each other.
+    fn find_overlapping<'r, 't>(&'r mut self, input: &'t str) -> OverlappingIter<'r, 't, Self>
+    where
+        Self: Regex<char>,
+    {
+        OverlappingIter::new(self, input)
+    }
+
+    /// Replace the first match in the input (as found by `find`) with `replacement`, which can be
+    /// a string that refers to groups as `$name`, or a closure (see `Replacer`). If there is no
+    /// match, the input is returned as it is.
+    ///
+    /// Matches are leftmost-longest, where the `regex` crate's are leftmost-first (the earliest
+    /// alternative wins), so the two differ when an alternative matches a prefix of a later one:
+    /// replacing `a|ab` in "ab" replaces all of it here, but only the "a" in the `regex` crate.
+    /// The same goes for `replace_all`, `split` and `splitn`. To find the same matches as the
+    /// `regex` crate, use `find_iter` with `MatchKind::LeftmostFirst`.
+    fn replace<'t>(&mut self, input: &'t str, replacement: impl Replacer) -> Cow<'t, str>
+    where
+        Self: Regex<char>,
+    {
+        replace::replacen(self, input, 1, replacement)
+    }
+
+    /// Replace every match in the input (as found by `find_iter`) with `replacement`, like
+    /// `replace`.
+    fn replace_all<'t>(&mut self, input: &'t str, replacement: impl Replacer) -> Cow<'t, str>
+    where
+        Self: Regex<char>,
+    {
+        replace::replacen(self, input, 0, replacement)
+    }
+
+    /// Iterate over the pieces of the input between the matches of this regex (as found by
+    /// `find_iter`), including empty pieces at the start and end.
+    fn split<'r, 't>(&'r mut self, input: &'t str) -> Split<'r, 't, Self>
+    where
+        Self: Regex<char>,
+    {
+        Split::new(self, input)
+    }
+
+    /// Like `split`, but producing at most `limit` pieces: the last is the rest of the input.
+    fn splitn<'r, 't>(&'r mut self, input: &'t str, limit: usize) -> SplitN<'r, 't, Self>
+    where
+        Self: Regex<char>,
+    {
+        SplitN::new(self, input, limit)
+    }
 }
 
 /*******************/
 /* Char Predicates */
 /*******************/
 
-trait Predicate: Copy {
-    fn matches(&self, ch: char) -> bool;
+/// The type of the symbols that a `Regex<T>` matches sequences of: `char` for text, or any other
+/// `Copy` type, like tokens or events. Only chars have the `CharSet` classes used for syntax.
+pub trait Symbol: Copy {
+    /// The set of chars in `ranges`, if this is `char`.
+    fn char_set(_ranges: &[(Self, Self)]) -> Option<CharSet> {
+        None
+    }
 }
 
-#[derive(Clone, Copy)]
-struct SingleChar<P: Predicate> {
+impl Symbol for char {
+    fn char_set(ranges: &[(char, char)]) -> Option<CharSet> {
+        Some(CharSet::new(ranges.to_vec()))
+    }
+}
+
+impl Symbol for bool {}
+impl Symbol for u8 {}
+impl Symbol for u16 {}
+impl Symbol for u32 {}
+impl Symbol for u64 {}
+impl Symbol for usize {}
+impl Symbol for i8 {}
+impl Symbol for i16 {}
+impl Symbol for i32 {}
+impl Symbol for i64 {}
+impl Symbol for isize {}
+impl<T: ?Sized> Symbol for &T {}
+
+/// A test on single chars (or other symbols). `combinators::one_of(predicate)` is a `Regex` that
+/// matches any single char that passes the test. The `predicates` module has functions for
+/// building them, and any `Fn(char) -> bool` closure is also a predicate.
+pub trait Predicate<T: Symbol = char>: Clone {
+    fn matches(&self, ch: T) -> bool;
+
+    /// Does this predicate match every char? It is always safe to return false.
+    fn is_universal(&self) -> bool {
+        false
+    }
+
+    /// The set of chars that this predicate matches, if it is known, so that it can be shown as a
+    /// class by `Regex.syntax()`.
+    fn char_set(&self) -> Option<CharSet> {
+        None
+    }
+}
+
+impl<T: Symbol, F: Fn(T) -> bool + Clone> Predicate<T> for F {
+    fn matches(&self, ch: T) -> bool {
+        self(ch)
+    }
+}
+
+#[derive(Clone)]
+struct SingleChar<P> {
     predicate: P,
     state: SimpleState,
+    start_tags: Option<Tags>,
+    end_tags: Option<Tags>,
 }
 
-impl<P: Predicate> SingleChar<P> {
+impl<P> SingleChar<P> {
     fn new(predicate: P) -> SingleChar<P> {
         SingleChar {
             predicate,
             state: SimpleState::Neither,
+            start_tags: None,
+            end_tags: None,
         }
     }
 }
 
-impl<P: Predicate> Regex for SingleChar<P> {
+impl<T: Symbol, P: Predicate<T>> Regex<T> for SingleChar<P> {
     fn initialize(&mut self) {
         self.state = SimpleState::Neither;
+        self.start_tags = None;
+        self.end_tags = None;
     }
 
     fn start(&mut self) {
@@ -86,7 +400,7 @@ impl<P: Predicate> Regex for SingleChar<P> {
         }
     }
 
-    fn advance(&mut self, ch: char) {
+    fn advance(&mut self, ch: T) {
         use SimpleState::*;
 
         if self.predicate.matches(ch) {
@@ -111,6 +425,37 @@ impl<P: Predicate> Regex for SingleChar<P> {
     fn is_dead(&self) -> bool {
         self.state == SimpleState::Neither
     }
+
+    fn matches_every_char(&self) -> bool {
+        self.predicate.is_universal()
+    }
+
+    fn syntax(&self) -> Syntax {
+        match self.predicate.char_set() {
+            Some(class) => Syntax::Class(class),
+            None => Syntax::Opaque(syntax::type_name::<P>()),
+        }
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, _pos: Pos) {
+        self.start_tags = Tags::prefer(self.start_tags.take(), Some(tags.clone()));
+        self.start();
+    }
+
+    fn advance_tagged(&mut self, ch: T, _pos: Pos) {
+        let tags = self.start_tags.take();
+        self.advance(ch);
+        self.end_tags = if self.accepts() { tags } else { None };
+    }
+
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        self.end_tags.clone()
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.push(self.state as u8);
+        true
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -125,58 +470,83 @@ enum SimpleState {
 /* Single Char Regexes */
 /***********************/
 
+// The symbol type is a parameter so that `Dot` (like `Empty`) only implements `Predicate<T>` for
+// one `T`, which keeps method calls on it unambiguous.
 #[derive(Clone, Copy)]
-struct Dot;
+struct Dot<T>(PhantomData<T>);
+
+impl<T: Symbol> Predicate<T> for Dot<T> {
+    fn matches(&self, _ch: T) -> bool {
+        true
+    }
 
-impl Predicate for Dot {
-    fn matches(&self, _ch: char) -> bool {
+    fn is_universal(&self) -> bool {
         true
     }
+
+    fn char_set(&self) -> Option<CharSet> {
+        T::char_set(&[]).map(|none| none.negate())
+    }
 }
 
 #[derive(Clone, Copy)]
-struct Char(char);
+struct Char<T>(T);
 
-impl Predicate for Char {
-    fn matches(&self, ch: char) -> bool {
+impl<T: Symbol + Eq> Predicate<T> for Char<T> {
+    fn matches(&self, ch: T) -> bool {
         self.0 == ch
     }
+
+    fn char_set(&self) -> Option<CharSet> {
+        T::char_set(&[(self.0, self.0)])
+    }
 }
 
 #[derive(Clone, Copy)]
-struct CharRange(char, char);
+struct CharRange<T>(T, T);
 
-impl Predicate for CharRange {
-    fn matches(&self, ch: char) -> bool {
+impl<T: Symbol + Ord> Predicate<T> for CharRange<T> {
+    fn matches(&self, ch: T) -> bool {
         self.0 <= ch && ch <= self.1
     }
+
+    fn char_set(&self) -> Option<CharSet> {
+        T::char_set(&[(self.0, self.1)])
+    }
 }
 
 /*********/
 /* Empty */
 /*********/
 
-#[derive(Clone, Copy)]
-struct Empty {
+#[derive(Clone)]
+struct Empty<T> {
     empty: bool,
+    tags: Option<Tags>,
+    symbol: PhantomData<T>,
 }
 
-impl Empty {
-    fn new() -> Empty {
-        Empty { empty: false }
+impl<T> Empty<T> {
+    fn new() -> Empty<T> {
+        Empty {
+            empty: false,
+            tags: None,
+            symbol: PhantomData,
+        }
     }
 }
 
-impl Regex for Empty {
+impl<T: Symbol> Regex<T> for Empty<T> {
     fn initialize(&mut self) {
         self.empty = false;
+        self.tags = None;
     }
 
     fn start(&mut self) {
         self.empty = true;
     }
 
-    fn advance(&mut self, _: char) {
+    fn advance(&mut self, _: T) {
         self.empty = false;
     }
 
@@ -187,6 +557,29 @@ impl Regex for Empty {
     fn is_dead(&self) -> bool {
         !self.empty
     }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Empty
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, _pos: Pos) {
+        self.tags = Tags::prefer(self.tags.take(), Some(tags.clone()));
+        self.start();
+    }
+
+    fn advance_tagged(&mut self, ch: T, _pos: Pos) {
+        self.tags = None;
+        self.advance(ch);
+    }
+
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        self.tags.clone()
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.push(self.empty as u8);
+        true
+    }
 }
 
 /********/
@@ -194,23 +587,26 @@ impl Regex for Empty {
 /********/
 
 #[derive(Clone)]
-struct Star<P: Regex> {
+struct Star<P> {
     init: bool,
+    init_tags: Option<Tags>,
     state: P,
 }
 
-impl<P: Regex> Star<P> {
+impl<P> Star<P> {
     fn new(regex: P) -> Star<P> {
         Star {
             init: false,
+            init_tags: None,
             state: regex,
         }
     }
 }
 
-impl<P: Regex> Regex for Star<P> {
+impl<T: Symbol, P: Regex<T>> Regex<T> for Star<P> {
     fn initialize(&mut self) {
         self.init = false;
+        self.init_tags = None;
         self.state.initialize();
     }
 
@@ -219,7 +615,7 @@ impl<P: Regex> Regex for Star<P> {
         self.state.start();
     }
 
-    fn advance(&mut self, ch: char) {
+    fn advance(&mut self, ch: T) {
         self.init = false;
         self.state.advance(ch);
         if self.state.accepts() {
@@ -235,6 +631,52 @@ impl<P: Regex> Regex for Star<P> {
     fn is_dead(&self) -> bool {
         !self.init && self.state.is_dead()
     }
+
+    fn is_saturated(&self) -> bool {
+        // If the empty string is tracked and `regex` matches any char, then every continuation
+        // is another occurrence.
+        (self.init && self.state.matches_every_char()) || self.state.is_saturated()
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.state.context(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        self.state.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Star(Box::new(self.state.syntax()))
+    }
+
+    // Going round again is preferred to leaving (see `Tags::choose`).
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.init = true;
+        self.init_tags = Tags::prefer(self.init_tags.take(), Some(tags.choose(1)));
+        self.state.start_tagged(&tags.choose(0), pos);
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        self.init = false;
+        self.init_tags = None;
+        self.state.advance_tagged(ch, pos);
+        if let Some(tags) = self.state.accepted_tags(pos) {
+            self.init = true;
+            self.state.start_tagged(&tags.choose(0), pos);
+            self.init_tags = Some(tags.choose(1));
+        }
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        let finished = self.state.accepted_tags(pos).map(|tags| tags.choose(1));
+        Tags::prefer(finished, self.init_tags.clone())
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.push(self.init as u8);
+        self.state.write_state(out)
+    }
 }
 
 /*********/
@@ -242,23 +684,26 @@ impl<P: Regex> Regex for Star<P> {
 /*********/
 
 #[derive(Clone)]
-struct Maybe<P: Regex> {
+struct Maybe<P> {
     init: bool,
+    init_tags: Option<Tags>,
     state: P,
 }
 
-impl<P: Regex> Maybe<P> {
+impl<P> Maybe<P> {
     fn new(regex: P) -> Maybe<P> {
         Maybe {
             init: false,
+            init_tags: None,
             state: regex,
         }
     }
 }
 
-impl<P: Regex> Regex for Maybe<P> {
+impl<T: Symbol, P: Regex<T>> Regex<T> for Maybe<P> {
     fn initialize(&mut self) {
         self.init = false;
+        self.init_tags = None;
         self.state.initialize();
     }
 
@@ -267,7 +712,7 @@ impl<P: Regex> Regex for Maybe<P> {
         self.state.start();
     }
 
-    fn advance(&mut self, ch: char) {
+    fn advance(&mut self, ch: T) {
         self.init = false;
         self.state.advance(ch);
     }
@@ -279,6 +724,328 @@ impl<P: Regex> Regex for Maybe<P> {
     fn is_dead(&self) -> bool {
         !self.init && self.state.is_dead()
     }
+
+    fn is_saturated(&self) -> bool {
+        self.state.is_saturated()
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.state.context(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        self.state.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Maybe(Box::new(self.state.syntax()))
+    }
+
+    // Matching `regex` is preferred to skipping it (see `Tags::choose`).
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.init = true;
+        self.init_tags = Tags::prefer(self.init_tags.take(), Some(tags.choose(1)));
+        self.state.start_tagged(&tags.choose(0), pos);
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        self.init = false;
+        self.init_tags = None;
+        self.state.advance_tagged(ch, pos);
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        Tags::prefer(self.state.accepted_tags(pos), self.init_tags.clone())
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.push(self.init as u8);
+        self.state.write_state(out)
+    }
+}
+
+/**********/
+/* Repeat */
+/**********/
+
+/// Between `min` and `max` occurrences of a regex, or at least `min` if there is no `max`.
+///
+/// Strings that have completed a different number of occurrences need to be tracked separately,
+/// so there is one copy of the regex per count: `copies[k]` tracks the strings that have
+/// completed `k` occurrences and are part way through the next one. If there is no `max`, all
+/// counts from `min - 1` on reach `min` when they complete an occurrence, so they share the last
+/// copy, which loops like a `Star`. So `plus` only keeps one copy, however deeply it's nested.
+///
+/// A regex that matches a single char from a class (see `Syntax::Class`) has nothing to track
+/// once the char is read: every tracked string has just completed some number of occurrences. So
+/// it is only stored once, to test chars with, and the counts that are `done` are the whole state.
+#[derive(Clone)]
+struct Repeat<P> {
+    min: usize,
+    max: Option<usize>,
+    /// One copy per count (see `copy`), or a single copy used to test chars with, if `single`.
+    copies: Vec<P>,
+    single: bool,
+    /// `done[k]`: is there a tracked string that has completed exactly `k` occurrences (or at
+    /// least `min`, for the last entry when there is no `max`)?
+    done: Vec<bool>,
+    done_tags: Vec<Option<Tags>>,
+}
+
+impl<P: Clone> Repeat<P> {
+    fn new<T: Symbol>(regex: P, min: usize, max: Option<usize>) -> Repeat<P>
+    where
+        P: Regex<T>,
+    {
+        let (copies, counts) = match max {
+            Some(max) => {
+                assert!(min <= max, "repeat: min is greater than max");
+                (max, max + 1)
+            }
+            None => (min.max(1), min + 1),
+        };
+        let single = copies > 0 && matches!(regex.syntax(), Syntax::Class(_));
+        Repeat {
+            min,
+            max,
+            copies: vec![regex; if single { 1 } else { copies }],
+            single,
+            done: vec![false; counts],
+            done_tags: vec![None; counts],
+        }
+    }
+
+    /// The copy that tracks the strings that have completed `k` occurrences, if they can take more.
+    fn copy(&self, k: usize) -> Option<usize> {
+        match self.max {
+            Some(_) => Some(k).filter(|&k| k < self.copies.len()),
+            None => Some(k.min(self.copies.len() - 1)),
+        }
+    }
+
+    /// The count after completing one more occurrence, from `copies[k]`.
+    fn next_count(&self, k: usize) -> usize {
+        match self.max {
+            Some(_) => k + 1,
+            None => (k + 1).min(self.min),
+        }
+    }
+
+    /// Record that a tracked string has just completed `k` occurrences, and start it on the next
+    /// one (which may complete immediately, if the regex matches the empty string).
+    fn complete<T: Symbol>(&mut self, mut k: usize)
+    where
+        P: Regex<T>,
+    {
+        while !self.done[k] {
+            self.done[k] = true;
+            let copy = match self.copy(k) {
+                Some(copy) => &mut self.copies[copy],
+                None => return,
+            };
+            copy.start();
+            if !copy.accepts() {
+                return;
+            }
+            k = self.next_count(k);
+        }
+    }
+
+    /// Does the single-char regex match `ch`?
+    fn single_matches<T: Symbol>(&mut self, ch: T) -> bool
+    where
+        P: Regex<T>,
+    {
+        let regex = &mut self.copies[0];
+        regex.initialize();
+        regex.start();
+        regex.advance(ch);
+        regex.accepts()
+    }
+
+    /// Can a string that has completed `k` occurrences go on to another?
+    fn can_go_on(&self, k: usize) -> bool {
+        self.max.is_none_or(|max| k < max)
+    }
+
+    /// Advance each string tracked by a single-char regex past one more occurrence.
+    fn shift_counts(&mut self) {
+        // The strings that could have stopped went on instead (see `accepted_tags`).
+        for k in self.min..self.done_tags.len() {
+            self.done_tags[k] = self.done_tags[k].take().map(|tags| tags.choose(0));
+        }
+        let last = self.done.len() - 1;
+        let (looped, looped_tags) = (self.done[last], self.done_tags[last].take());
+        self.done.rotate_right(1);
+        self.done_tags.rotate_right(1);
+        self.done[0] = false;
+        self.done_tags[0] = None;
+        // Without a `max`, strings that had completed at least `min` occurrences still have.
+        if self.max.is_none() {
+            self.done[last] |= looped;
+            self.done_tags[last] = Tags::prefer(looped_tags, self.done_tags[last].take());
+        }
+    }
+
+    fn complete_tagged<T: Symbol>(&mut self, mut k: usize, mut tags: Tags, pos: Pos)
+    where
+        P: Regex<T>,
+    {
+        loop {
+            // Once there are enough occurrences, going on is preferred to stopping, like in a
+            // `Star` (see `Tags::choose`).
+            let (stop, go_on) = if k >= self.min && self.can_go_on(k) {
+                (tags.choose(1), tags.choose(0))
+            } else {
+                (tags.clone(), tags)
+            };
+            self.done[k] = true;
+            self.done_tags[k] = Tags::prefer(self.done_tags[k].take(), Some(stop));
+            let next = self.next_count(k);
+            let copy = match self.copy(k) {
+                Some(copy) => &mut self.copies[copy],
+                None => return,
+            };
+            copy.start_tagged(&go_on, pos);
+            match copy.accepted_tags(pos) {
+                Some(next_tags) if next != k => {
+                    k = next;
+                    tags = next_tags;
+                }
+                // An empty occurrence on the looping copy doesn't change the count, but it can
+                // still set groups, like the first one in a `Star`.
+                Some(next_tags) => {
+                    self.done_tags[k] =
+                        Tags::prefer(self.done_tags[k].take(), Some(next_tags.choose(1)));
+                    return;
+                }
+                None => return,
+            }
+        }
+    }
+}
+
+impl<T: Symbol, P: Regex<T>> Regex<T> for Repeat<P> {
+    fn initialize(&mut self) {
+        for copy in &mut self.copies {
+            copy.initialize();
+        }
+        for k in 0..self.done.len() {
+            self.done[k] = false;
+            self.done_tags[k] = None;
+        }
+    }
+
+    fn start(&mut self) {
+        if self.single {
+            self.done[0] = true;
+        } else {
+            self.complete::<T>(0);
+        }
+    }
+
+    fn advance(&mut self, ch: T) {
+        if self.single {
+            if self.single_matches(ch) {
+                self.shift_counts();
+            } else {
+                self.done.fill(false);
+            }
+            return;
+        }
+        for k in 0..self.done.len() {
+            self.done[k] = false;
+        }
+        for copy in &mut self.copies {
+            copy.advance(ch);
+        }
+        for k in 0..self.copies.len() {
+            if self.copies[k].accepts() {
+                self.complete::<T>(self.next_count(k));
+            }
+        }
+    }
+
+    fn accepts(&self) -> bool {
+        self.done[self.min..].iter().any(|done| *done)
+    }
+
+    fn is_dead(&self) -> bool {
+        if self.single {
+            return !self.done.contains(&true);
+        }
+        !self.accepts() && self.copies.iter().all(|copy| copy.is_dead())
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        for copy in &mut self.copies {
+            copy.context(prev, next);
+        }
+    }
+
+    fn uses_context(&self) -> bool {
+        self.copies.iter().any(|copy| copy.uses_context())
+    }
+
+    fn syntax(&self) -> Syntax {
+        match self.copies.first() {
+            Some(regex) => Syntax::Repeat(Box::new(regex.syntax()), self.min, self.max),
+            // With a `max` of 0, there's no copy to describe, but it only matches the empty string.
+            None => Syntax::Empty,
+        }
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        if self.single {
+            self.done[0] = true;
+            self.done_tags[0] = Tags::prefer(self.done_tags[0].take(), Some(tags.clone()));
+        } else {
+            self.complete_tagged::<T>(0, tags.clone(), pos);
+        }
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        if self.single {
+            if self.single_matches(ch) {
+                self.shift_counts();
+            } else {
+                self.done.fill(false);
+                self.done_tags.fill(None);
+            }
+            return;
+        }
+        for k in 0..self.done.len() {
+            self.done[k] = false;
+            self.done_tags[k] = None;
+        }
+        for copy in &mut self.copies {
+            copy.advance_tagged(ch, pos);
+        }
+        for k in 0..self.copies.len() {
+            if let Some(tags) = self.copies[k].accepted_tags(pos) {
+                self.complete_tagged::<T>(self.next_count(k), tags, pos);
+            }
+        }
+    }
+
+    fn accepted_tags(&self, _pos: Pos) -> Option<Tags> {
+        // Prefer more occurrences, like `Star` does. Strings counted by a single-char regex only
+        // record that they stopped here now, since until now they could still go on.
+        (self.min..self.done_tags.len())
+            .rev()
+            .fold(None, |best, k| {
+                let tags = match &self.done_tags[k] {
+                    Some(tags) if self.single && self.can_go_on(k) => Some(tags.choose(1)),
+                    tags => tags.clone(),
+                };
+                Tags::prefer(best, tags)
+            })
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        out.extend(self.done.iter().map(|done| *done as u8));
+        self.single || self.copies.iter().all(|copy| copy.write_state(out))
+    }
 }
 
 /*******/
@@ -286,9 +1053,9 @@ impl<P: Regex> Regex for Maybe<P> {
 /*******/
 
 #[derive(Clone)]
-struct Alt<P: Regex, Q: Regex>(P, Q);
+struct Alt<P, Q>(P, Q);
 
-impl<P: Regex, Q: Regex> Regex for Alt<P, Q> {
+impl<T: Symbol, P: Regex<T>, Q: Regex<T>> Regex<T> for Alt<P, Q> {
     fn initialize(&mut self) {
         self.0.initialize();
         self.1.initialize();
@@ -299,7 +1066,7 @@ impl<P: Regex, Q: Regex> Regex for Alt<P, Q> {
         self.1.start();
     }
 
-    fn advance(&mut self, ch: char) {
+    fn advance(&mut self, ch: T) {
         self.0.advance(ch);
         self.1.advance(ch);
     }
@@ -311,6 +1078,45 @@ impl<P: Regex, Q: Regex> Regex for Alt<P, Q> {
     fn is_dead(&self) -> bool {
         self.0.is_dead() && self.1.is_dead()
     }
+
+    fn is_saturated(&self) -> bool {
+        self.0.is_saturated() || self.1.is_saturated()
+    }
+
+    fn matches_every_char(&self) -> bool {
+        self.0.matches_every_char() || self.1.matches_every_char()
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.0.context(prev, next);
+        self.1.context(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        self.0.uses_context() || self.1.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Alt(Box::new(self.0.syntax()), Box::new(self.1.syntax()))
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.0.start_tagged(&tags.choose(0), pos);
+        self.1.start_tagged(&tags.choose(1), pos);
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        self.0.advance_tagged(ch, pos);
+        self.1.advance_tagged(ch, pos);
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        Tags::prefer(self.0.accepted_tags(pos), self.1.accepted_tags(pos))
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        self.0.write_state(out) && self.1.write_state(out)
+    }
 }
 
 /*******/
@@ -318,9 +1124,9 @@ impl<P: Regex, Q: Regex> Regex for Alt<P, Q> {
 /*******/
 
 #[derive(Clone)]
-struct Seq<P: Regex, Q: Regex>(P, Q);
+struct Seq<P, Q>(P, Q);
 
-impl<P: Regex, Q: Regex> Regex for Seq<P, Q> {
+impl<T: Symbol, P: Regex<T>, Q: Regex<T>> Regex<T> for Seq<P, Q> {
     fn initialize(&mut self) {
         self.0.initialize();
         self.1.initialize();
@@ -333,7 +1139,7 @@ impl<P: Regex, Q: Regex> Regex for Seq<P, Q> {
         }
     }
 
-    fn advance(&mut self, ch: char) {
+    fn advance(&mut self, ch: T) {
         self.1.advance(ch);
         self.0.advance(ch);
         if self.0.accepts() {
@@ -348,19 +1154,59 @@ impl<P: Regex, Q: Regex> Regex for Seq<P, Q> {
     fn is_dead(&self) -> bool {
         self.0.is_dead() && self.1.is_dead()
     }
+
+    fn is_saturated(&self) -> bool {
+        self.1.is_saturated()
+    }
+
+    fn context(&mut self, prev: Option<T>, next: Option<T>) {
+        self.0.context(prev, next);
+        self.1.context(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        self.0.uses_context() || self.1.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        Syntax::Seq(Box::new(self.0.syntax()), Box::new(self.1.syntax()))
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.0.start_tagged(tags, pos);
+        if let Some(tags) = self.0.accepted_tags(pos) {
+            self.1.start_tagged(&tags, pos);
+        }
+    }
+
+    fn advance_tagged(&mut self, ch: T, pos: Pos) {
+        self.1.advance_tagged(ch, pos);
+        self.0.advance_tagged(ch, pos);
+        if let Some(tags) = self.0.accepted_tags(pos) {
+            self.1.start_tagged(&tags, pos);
+        }
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        self.1.accepted_tags(pos)
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        self.0.write_state(out) && self.1.write_state(out)
+    }
 }
 
 pub mod combinators {
     use super::*;
 
     /// Match only the empty string.
-    pub fn empty() -> impl Regex {
+    pub fn empty<T: Symbol>() -> impl Regex<T> {
         Empty::new()
     }
 
-    /// Match any single char.
+    /// Match any single char. For other symbol types, use `one_of(predicates::any())`.
     pub fn dot() -> impl Regex {
-        SingleChar::new(Dot)
+        SingleChar::new(Dot(PhantomData))
     }
 
     /// Match a single, specific, char.
@@ -371,30 +1217,157 @@ pub mod combinators {
     /// Match a char in the given range (in unicode code point order).
     /// The range is inclusive on both ends.
     pub fn char_range(min_ch: char, max_ch: char) -> impl Regex {
-        SingleChar::new(CharRange(min_ch as char, max_ch as char))
+        SingleChar::new(CharRange(min_ch, max_ch))
+    }
+
+    /// Match `ch`, ignoring case. This uses full case folding, so some chars match several: `ß`
+    /// matches `ß`, `ẞ`, `ss`, `SS`, etc.
+    pub fn achar_ci(ch: char) -> impl Regex {
+        literal_ci(ch.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Match a char that is in the given range (inclusive) up to case. This uses simple case
+    /// folding (one char to one char), so `[a-z]` matches `K` (the Kelvin sign) but not `ß`.
+    pub fn char_range_ci(min_ch: char, max_ch: char) -> impl Regex {
+        SingleChar::new(predicates::set(&[(min_ch, max_ch)]).case_fold())
+    }
+
+    /// Match `literal`, ignoring case. Like `achar_ci`, this uses full case folding, so
+    /// `"STRASSE"` matches `"Straße"`.
+    pub fn literal_ci(literal: &str) -> impl Regex {
+        case::FoldedLiteral::new(literal)
+    }
+
+    /// Match any single char that satisfies `predicate`.
+    pub fn one_of<T: Symbol>(predicate: impl Predicate<T>) -> impl Regex<T> {
+        SingleChar::new(predicate)
     }
 
     /// Recognize the sequence `first` then `second`. More precisely, match a string iff it can be
     /// split into a first and second half, such taht `first` matches the first half and `second`
     /// matches the second half.
-    pub fn seq(first: impl Regex, second: impl Regex) -> impl Regex {
+    pub fn seq<T: Symbol>(first: impl Regex<T>, second: impl Regex<T>) -> impl Regex<T> {
         Seq(first, second)
     }
 
     /// Match a string iff either `left` or `right` (or both) match it.
-    pub fn alt(left: impl Regex, right: impl Regex) -> impl Regex {
+    pub fn alt<T: Symbol>(left: impl Regex<T>, right: impl Regex<T>) -> impl Regex<T> {
         Alt(left, right)
     }
 
     /// Recognize zero or more occurrences of `regex`.
-    pub fn star(regex: impl Regex) -> impl Regex {
+    pub fn star<T: Symbol>(regex: impl Regex<T>) -> impl Regex<T> {
         Star::new(regex)
     }
 
     /// Recognize zero or one occurrences of `regex`.
-    pub fn maybe(regex: impl Regex) -> impl Regex {
+    pub fn maybe<T: Symbol>(regex: impl Regex<T>) -> impl Regex<T> {
         Maybe::new(regex)
     }
+
+    /// Recognize one or more occurrences of `regex`.
+    pub fn plus<T: Symbol>(regex: impl Regex<T>) -> impl Regex<T> {
+        Repeat::new(regex, 1, None)
+    }
+
+    /// Recognize exactly `n` occurrences of `regex`.
+    pub fn exactly<T: Symbol>(regex: impl Regex<T>, n: usize) -> impl Regex<T> {
+        Repeat::new(regex, n, Some(n))
+    }
+
+    /// Recognize between `min` and `max` occurrences of `regex` (inclusive), or at least `min` if
+    /// `max` is `None`. This keeps one copy of `regex`'s state per count, so it takes `max` (or
+    /// `min`, or 1) times as much space as `regex`, unless `regex` matches a single char from a
+    /// class (like `char_range`), when it only keeps a flag per count. Panics if `min` is greater
+    /// than `max`.
+    pub fn repeat<T: Symbol>(
+        regex: impl Regex<T>,
+        min: usize,
+        max: Option<usize>,
+    ) -> impl Regex<T> {
+        Repeat::new(regex, min, max)
+    }
+
+    /// Match the empty string, at the start of the input.
+    pub fn text_start() -> impl Regex {
+        Assert::new(Anchor::TextStart)
+    }
+
+    /// Match the empty string, at the end of the input.
+    pub fn text_end() -> impl Regex {
+        Assert::new(Anchor::TextEnd)
+    }
+
+    /// Match the empty string, at the start of the input or just after a `\n`.
+    pub fn line_start() -> impl Regex {
+        Assert::new(Anchor::LineStart)
+    }
+
+    /// Match the empty string, at the end of the input or just before a `\n`.
+    pub fn line_end() -> impl Regex {
+        Assert::new(Anchor::LineEnd)
+    }
+
+    /// Match the empty string, between a word char (see `predicates::word`) and a non-word char,
+    /// or the start or end of the input. This is `\b` in patterns.
+    pub fn word_boundary() -> impl Regex {
+        Assert::new(Anchor::WordBoundary)
+    }
+
+    /// Match the empty string, anywhere `word_boundary()` doesn't. This is `\B` in patterns.
+    pub fn not_word_boundary() -> impl Regex {
+        Assert::new(Anchor::NotWordBoundary)
+    }
+
+    /// Match the empty string, if `regex` matches the next char, or the empty string at the end
+    /// of the input. Only one char of lookahead is available, so `regex` should match strings of
+    /// at most one char, like `one_of(..)`.
+    pub fn lookahead(regex: impl Regex) -> impl Regex {
+        Assert::new(assertions::Look::new(regex, false))
+    }
+
+    /// Match the empty string, if `regex` matches the previous char, or the empty string at the
+    /// start of the input. Like `lookahead`, this only sees one char.
+    pub fn lookbehind(regex: impl Regex) -> impl Regex {
+        Assert::new(assertions::Look::new(regex, true))
+    }
+
+    /// Match a string iff both `left` and `right` match it. This keeps a separate copy of `left` and
+    /// `right` for each tracked string, since they must both accept the _same_ string. Copies in
+    /// the same state are merged if the regexes support snapshots (see `Regex.write_state`);
+    /// otherwise there can be one per position in the input. Groups in both halves are reported.
+    pub fn intersect<T: Symbol>(left: impl Regex<T>, right: impl Regex<T>) -> impl Regex<T> {
+        boolean::Intersect::new(left, right)
+    }
+
+    /// Match a string iff `regex` does _not_ match it. Like `intersect`, this keeps a copy of
+    /// `regex` for each tracked string. Groups inside `regex` are not reported.
+    pub fn complement<T: Symbol>(regex: impl Regex<T>) -> impl Regex<T> {
+        boolean::Complement::new(regex)
+    }
+
+    /// Match a string iff at most `max_edits` insertions, deletions and substitutions of chars
+    /// turn it into a string that `regex` matches. Like `intersect`, this keeps a copy of `regex`
+    /// for each way of editing each tracked string, merged if `regex` supports snapshots. Deleted
+    /// and substituted chars are tried one per range of chars that `regex.syntax()` treats the
+    /// same, and assertions see the input around them rather than the edited string. Groups
+    /// inside `regex` are not reported. Use `fuzzy_match_cost` to find the fewest edits needed.
+    /// Fails if part of `regex` has no syntax (see `NoSyntaxError`), like `lookahead` or a
+    /// closure passed to `one_of`, since then it isn't known which chars to try.
+    pub fn fuzzy(regex: impl Regex, max_edits: usize) -> Result<impl Regex, NoSyntaxError> {
+        fuzzy::Fuzzy::new(regex, max_edits)
+    }
+
+    /// Match the same strings as `regex`, but record where it matched under `name`, to be
+    /// reported by `Regex.captures(&str)`.
+    pub fn group<T: Symbol>(name: &str, regex: impl Regex<T>) -> impl Regex<T> {
+        Group::new(name, regex)
+    }
+
+    /// Erase the type of `regex`, so that it can be stored alongside regexes of other types.
+    pub fn boxed<T: Symbol>(regex: impl Regex<T> + Send + Sync + 'static) -> BoxedRegex<T> {
+        BoxedRegex::new(regex)
+    }
 }
 
 #[cfg(test)]
@@ -447,6 +1420,209 @@ mod tests {
         assert!(!integer.is_match("1101021"));
     }
 
+    #[test]
+    fn repeat() {
+        use combinators::*;
+
+        let a = || achar('a');
+        let b = || achar('b');
+        let mut cases: Vec<(&str, BoxedRegex)> = vec![
+            ("(a|b){2,4}", boxed(repeat(alt(a(), b()), 2, Some(4)))),
+            ("a{3}", boxed(exactly(a(), 3))),
+            ("a{0}", boxed(exactly(a(), 0))),
+            ("(ab?)+", boxed(plus(seq(a(), maybe(b()))))),
+            ("(ab){2,}", boxed(repeat(seq(a(), b()), 2, None))),
+            ("(a*b){1,2}", boxed(repeat(seq(star(a()), b()), 1, Some(2)))),
+            // The repeated regex matches the empty string
+            (
+                "(a?b?){2,3}",
+                boxed(repeat(seq(maybe(a()), maybe(b())), 2, Some(3))),
+            ),
+            ("(a|b?){3,}", boxed(repeat(alt(a(), maybe(b())), 3, None))),
+            // Single chars only keep a flag per count.
+            ("[ab]{2,4}", boxed(repeat(char_range('a', 'b'), 2, Some(4)))),
+            ("a{0,2}", boxed(repeat(a(), 0, Some(2)))),
+            ("b{2,}", boxed(repeat(b(), 2, None))),
+            ("a*", boxed(repeat(a(), 0, None))),
+            ("(a{2})+", boxed(plus(exactly(a(), 2)))),
+        ];
+
+        // Every string of `a`s and `b`s, up to length 7.
+        let mut inputs = vec![String::new()];
+        for len in 1..=7 {
+            for bits in 0..(1 << len) {
+                let input = (0..len)
+                    .map(|i| if bits & (1 << i) == 0 { 'a' } else { 'b' })
+                    .collect();
+                inputs.push(input);
+            }
+        }
+
+        for (pattern, regex) in &mut cases {
+            let expected = regex::Regex::new(&format!("^(?:{})$", pattern)).unwrap();
+            for input in &inputs {
+                assert_eq!(
+                    regex.is_match(input),
+                    expected.is_match(input),
+                    "{} on {:?}",
+                    pattern,
+                    input
+                );
+            }
+        }
+
+        let mut pair = seq(
+            exactly(group("digit", char_range('0', '9')), 2),
+            plus(achar('!')),
+        );
+        let caps = pair.captures("42!!").unwrap();
+        assert_eq!(caps.get("digit").unwrap().bytes(), 1..2);
+        let mut pair = seq(
+            group("digits", exactly(char_range('0', '9'), 2)),
+            plus(achar('!')),
+        );
+        let caps = pair.captures("42!!").unwrap();
+        assert_eq!(caps.get("digits").unwrap().bytes(), 0..2);
+
+        let mut many = exactly(char_range('0', '9'), 100_000);
+        assert!(!many.is_match("123"));
+
+        // `plus` keeps a single copy, so nesting it doesn't double the state each time.
+        let nested = format!("{}a{}", "(".repeat(40), ")+".repeat(40));
+        let mut nested = Pattern::new(&nested).unwrap();
+        assert!(nested.is_match("aaaa"));
+        assert!(!nested.is_match("aaab"));
+        let mut nested = (0..40).fold(boxed(seq(a(), b())), |regex, _| boxed(plus(regex)));
+        assert!(nested.is_match("ababab"));
+        assert!(!nested.is_match("abba"));
+    }
+
+    // Passes everything through to `regex`, counting the chars it is advanced by.
+    #[derive(Clone)]
+    struct Counting<R: Regex> {
+        regex: R,
+        advances: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl<R: Regex> Regex for Counting<R> {
+        fn initialize(&mut self) {
+            self.regex.initialize();
+        }
+
+        fn start(&mut self) {
+            self.regex.start();
+        }
+
+        fn advance(&mut self, ch: char) {
+            self.advances.set(self.advances.get() + 1);
+            self.regex.advance(ch);
+        }
+
+        fn accepts(&self) -> bool {
+            self.regex.accepts()
+        }
+
+        fn is_dead(&self) -> bool {
+            self.regex.is_dead()
+        }
+
+        fn is_saturated(&self) -> bool {
+            self.regex.is_saturated()
+        }
+    }
+
+    #[test]
+    fn saturation() {
+        use combinators::*;
+
+        let advances = std::rc::Rc::new(std::cell::Cell::new(0));
+        let mut prefix = Counting {
+            regex: seq(seq(achar('a'), achar('b')), alt(star(dot()), achar('!'))),
+            advances: advances.clone(),
+        };
+        assert!(prefix.is_match("ab and then a lot more"));
+        assert_eq!(advances.get(), 2);
+        assert!(!prefix.is_match("ba and then a lot more"));
+        assert!(prefix.is_match("ab"));
+        assert_eq!(
+            prefix.find_overlapping("xab, yab").collect::<Vec<_>>(),
+            [1..8, 6..8]
+        );
+
+        let mut regex = seq(maybe(achar('a')), star(one_of(predicates::any())));
+        regex.initialize();
+        assert!(!regex.is_saturated());
+        regex.start();
+        assert!(regex.is_saturated());
+        let mut regex = star(one_of(predicates::none_of(&[])));
+        regex.initialize();
+        regex.start();
+        assert!(regex.is_saturated());
+        // Correct, but not known to be saturated.
+        let mut regex = star(one_of(|_| true));
+        regex.initialize();
+        regex.start();
+        assert!(!regex.is_saturated());
+        assert!(regex.is_match("anything"));
+        // An `a`, followed by one or more chars.
+        let mut regex = seq(achar('a'), seq(dot(), star(dot())));
+        regex.initialize();
+        regex.start();
+        regex.advance('a');
+        assert!(!regex.is_saturated());
+        regex.advance('b');
+        assert!(regex.is_saturated());
+    }
+
+    #[test]
+    fn symbols() {
+        use combinators::*;
+        use predicates::{any, is, not, range};
+
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        enum Event {
+            Open,
+            Read,
+            Write,
+            Close,
+        }
+        impl Symbol for Event {}
+        use Event::*;
+
+        // Every `Open` is followed by reads and writes, then a `Close`.
+        let session = seq(
+            one_of(is(Open)),
+            seq(star(one_of(range(Read, Write))), one_of(is(Close))),
+        );
+        let mut audit = star(session);
+        assert!(audit.is_match_symbols([]));
+        assert!(audit.is_match_symbols([Open, Read, Write, Close, Open, Close]));
+        assert!(!audit.is_match_symbols([Open, Read]));
+        assert!(!audit.is_match_symbols(vec![Open, Open, Close, Close]));
+
+        // A write that is never followed by a close.
+        let mut unsaved = seq(
+            star(one_of(any())),
+            seq(one_of(is(Write)), star(one_of(not(is(Close))))),
+        );
+        assert!(unsaved.is_match_symbols([Open, Write, Read]));
+        assert!(!unsaved.is_match_symbols([Open, Write, Close]));
+        let mut safe = intersect(audit.clone(), complement(unsaved));
+        assert!(safe.is_match_symbols([Open, Write, Close]));
+        assert!(!safe.is_match_symbols([Open, Write, Read]));
+
+        // Other symbol types work too, and so do the char regexes that the combinators now share.
+        let mut bits = seq(
+            group("ones", plus(one_of(is(1u8)))),
+            exactly(one_of(is(0)), 2),
+        );
+        assert!(bits.is_match_symbols([1, 1, 0, 0]));
+        assert!(!bits.is_match_symbols([1, 0]));
+        assert!(seq(plus(achar('a')), empty()).is_match("aa"));
+        // Only chars have classes to show.
+        assert_eq!(one_of(range(1u32, 9)).syntax().to_string(), "<CharRange>");
+    }
+
     // ~6ns / char
     #[bench]
     fn this_crate(bencher: &mut Bencher) {
@@ -465,6 +1641,23 @@ mod tests {
         });
     }
 
+    #[bench]
+    fn this_crate_dfa(bencher: &mut Bencher) {
+        use combinators::*;
+
+        let integer = alt(
+            achar('0'),
+            seq(char_range('1', '9'), star(char_range('0', '9'))),
+        );
+        let tail = seq(achar('.'), star(char_range('0', '9')));
+        let mut decimal = compile_dfa(seq(integer, maybe(tail)));
+
+        bencher.iter(|| {
+            assert!(decimal.is_match(ANUM));
+            assert!(!decimal.is_match(NOTANUM));
+        });
+    }
+
     // Burnt Sushi's Regexes.
     // It's 3 times faster on this example on my laptop.
     #[bench]
@@ -476,4 +1669,31 @@ mod tests {
             assert!(!number.is_match(NOTANUM));
         })
     }
+
+    // 100 chars in total (50 each)
+    const GROUPS: &str = "1234-56789-012-3456-78901-234-5678-90123-456-78901";
+    const NOTGROUPS: &str = "1234-56789-012-3456-78901-234-5678-901234-56-78901";
+
+    #[bench]
+    fn this_crate_repeat(bencher: &mut Bencher) {
+        use combinators::*;
+
+        let digits = || repeat(char_range('0', '9'), 3, Some(5));
+        let mut groups = seq(star(seq(digits(), achar('-'))), digits());
+
+        bencher.iter(|| {
+            assert!(groups.is_match(GROUPS));
+            assert!(!groups.is_match(NOTGROUPS));
+        });
+    }
+
+    #[bench]
+    fn regex_crate_repeat(bencher: &mut Bencher) {
+        use regex::Regex;
+        let groups = Regex::new("^([0-9]{3,5}-)*[0-9]{3,5}$").unwrap();
+        bencher.iter(|| {
+            assert!(groups.is_match(GROUPS));
+            assert!(!groups.is_match(NOTGROUPS));
+        })
+    }
 }
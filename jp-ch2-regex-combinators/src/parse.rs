@@ -0,0 +1,986 @@
+use super::*;
+use assertions::{Anchor, Assert};
+use case::FoldedLiteral;
+use predicates::CharSet;
+use std::error::Error;
+use std::fmt;
+
+/***********/
+/* Pattern */
+/***********/
+
+/// A regex compiled at runtime from a pattern string, like `(0|[1-9][0-9]*)(\.[0-9]*)?`.
+///
+/// The combinators all return distinct static types, so a tree whose shape is only known at
+/// runtime can't be built out of them directly. Instead, a `Pattern` is a tree of the same `Seq`,
+/// `Alt`, `Star`, etc. nodes, wrapped in an enum.
+///
+/// # Syntax
+///
+/// - `.` matches any char, and any other char matches itself, except for the special chars
+///   `\.+*?()|[{^$`, which can be matched by escaping them with a backslash (as can any other
+///   ASCII punctuation or space, except `<` and `>`). `\n`, `\r` and `\t` match a newline,
+///   carriage return, and tab, and `\x7F` or `\x{1F600}` the char with the given (hex) code point.
+/// - `[a-z0-9_]` matches any char in the class, and `[^a-z0-9_]` any char not in it. Escapes work
+///   the same way inside classes, and `-` can be matched by putting it first or last.
+/// - `\d`, `\w` and `\s` match unicode digits, word chars and whitespace, and `\D`, `\W` and `\S`
+///   match anything else. `\p{Greek}` or `\pL` matches a char in the given unicode script, general
+///   category, or property, and `\P{Greek}` matches a char that isn't. These also work inside
+///   classes.
+/// - `^` and `$` match the empty string at the start and end of the input, and `\b` and `\B` at a
+///   word boundary and anywhere else (see `combinators::word_boundary`). `\A` and `\z` always
+///   match at the start and end of the input, even in multi-line mode.
+/// - `xy` is a sequence, and `x|y` an alternation.
+/// - `x*`, `x+` and `x?` match zero or more, one or more, and zero or one `x`s. `x{n}`, `x{m,}`
+///   and `x{m,n}` match exactly `n`, at least `m`, and between `m` and `n` `x`s. Patterns that
+///   would be too large once repetitions are expanded, like `(a{1000}){1000}`, are rejected.
+/// - `(x)` is a group, named after its position: the first group is named `"1"`, the second
+///   `"2"`, and so on. `(?P<name>x)` or `(?<name>x)` is a group with the given name, and `(?:x)`
+///   is not a group at all.
+/// - `(?i)` makes the rest of the enclosing group (or pattern) case-insensitive, and `(?-i)` makes
+///   it case-sensitive again. `(?i:x)` matches `x` case-insensitively. Literals use full case
+///   folding (see `combinators::literal_ci`) and classes simple case folding.
+/// - `(?m)` turns on multi-line mode, where `^` and `$` also match at the start and end of each
+///   line. Flags can be combined, as in `(?im)` or `(?i-m:x)`.
+#[derive(Clone)]
+pub struct Pattern(Node);
+
+impl Pattern {
+    /// Parse a pattern string.
+    pub fn new(pattern: &str) -> Result<Pattern, ParseError> {
+        let mut parser = Parser {
+            pattern,
+            pos: 0,
+            groups: 0,
+            flags: Flags::default(),
+        };
+        let node = parser.parse_alt()?;
+        match parser.peek() {
+            None => Ok(Pattern(node)),
+            Some(')') => Err(parser.error_here(ParseErrorKind::UnopenedGroup)),
+            Some(_) => unreachable!("parse_alt only stops at ')' or the end"),
+        }
+    }
+}
+
+impl Regex for Pattern {
+    fn initialize(&mut self) {
+        self.0.initialize();
+    }
+
+    fn start(&mut self) {
+        self.0.start();
+    }
+
+    fn advance(&mut self, ch: char) {
+        self.0.advance(ch);
+    }
+
+    fn accepts(&self) -> bool {
+        self.0.accepts()
+    }
+
+    fn is_dead(&self) -> bool {
+        self.0.is_dead()
+    }
+
+    fn is_saturated(&self) -> bool {
+        self.0.is_saturated()
+    }
+
+    fn matches_every_char(&self) -> bool {
+        self.0.matches_every_char()
+    }
+
+    fn context(&mut self, prev: Option<char>, next: Option<char>) {
+        self.0.context(prev, next);
+    }
+
+    fn uses_context(&self) -> bool {
+        self.0.uses_context()
+    }
+
+    fn syntax(&self) -> Syntax {
+        self.0.syntax()
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        self.0.start_tagged(tags, pos);
+    }
+
+    fn advance_tagged(&mut self, ch: char, pos: Pos) {
+        self.0.advance_tagged(ch, pos);
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        self.0.accepted_tags(pos)
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        self.0.write_state(out)
+    }
+}
+
+/// Shows the pattern in canonical form (see `Regex.syntax()`), which may differ from the string
+/// it was parsed from, e.g. in its use of `(?:..)` or flags.
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.syntax(), f)
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.syntax(), f)
+    }
+}
+
+#[derive(Clone)]
+enum Node {
+    Empty(Empty<char>),
+    Dot(SingleChar<Dot<char>>),
+    Char(SingleChar<Char<char>>),
+    Class(SingleChar<CharSet>),
+    Folded(FoldedLiteral),
+    Assert(Assert<Anchor>),
+    Seq(Box<Seq<Node, Node>>),
+    Alt(Box<Alt<Node, Node>>),
+    Star(Box<Star<Node>>),
+    Maybe(Box<Maybe<Node>>),
+    Repeat(Box<Repeat<Node>>),
+    Group(Box<Group<Node>>),
+}
+
+macro_rules! dispatch {
+    ($node:expr, $regex:ident => $body:expr) => {
+        match $node {
+            Node::Empty($regex) => $body,
+            Node::Dot($regex) => $body,
+            Node::Char($regex) => $body,
+            Node::Class($regex) => $body,
+            Node::Folded($regex) => $body,
+            Node::Assert($regex) => $body,
+            Node::Seq($regex) => $body,
+            Node::Alt($regex) => $body,
+            Node::Star($regex) => $body,
+            Node::Maybe($regex) => $body,
+            Node::Repeat($regex) => $body,
+            Node::Group($regex) => $body,
+        }
+    };
+}
+
+impl Regex for Node {
+    fn initialize(&mut self) {
+        dispatch!(self, regex => regex.initialize())
+    }
+
+    fn start(&mut self) {
+        dispatch!(self, regex => regex.start())
+    }
+
+    fn advance(&mut self, ch: char) {
+        dispatch!(self, regex => regex.advance(ch))
+    }
+
+    fn accepts(&self) -> bool {
+        dispatch!(self, regex => regex.accepts())
+    }
+
+    fn is_dead(&self) -> bool {
+        dispatch!(self, regex => regex.is_dead())
+    }
+
+    fn is_saturated(&self) -> bool {
+        dispatch!(self, regex => regex.is_saturated())
+    }
+
+    fn matches_every_char(&self) -> bool {
+        dispatch!(self, regex => regex.matches_every_char())
+    }
+
+    fn context(&mut self, prev: Option<char>, next: Option<char>) {
+        dispatch!(self, regex => regex.context(prev, next))
+    }
+
+    fn uses_context(&self) -> bool {
+        dispatch!(self, regex => regex.uses_context())
+    }
+
+    fn syntax(&self) -> Syntax {
+        dispatch!(self, regex => regex.syntax())
+    }
+
+    fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+        dispatch!(self, regex => regex.start_tagged(tags, pos))
+    }
+
+    fn advance_tagged(&mut self, ch: char, pos: Pos) {
+        dispatch!(self, regex => regex.advance_tagged(ch, pos))
+    }
+
+    fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+        dispatch!(self, regex => regex.accepted_tags(pos))
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) -> bool {
+        dispatch!(self, regex => regex.write_state(out))
+    }
+}
+
+impl Node {
+    fn seq(first: Node, second: Node) -> Node {
+        Node::Seq(Box::new(Seq(first, second)))
+    }
+
+    fn alt(left: Node, right: Node) -> Node {
+        Node::Alt(Box::new(Alt(left, right)))
+    }
+
+    fn star(node: Node) -> Node {
+        Node::Star(Box::new(Star::new(node)))
+    }
+
+    fn maybe(node: Node) -> Node {
+        Node::Maybe(Box::new(Maybe::new(node)))
+    }
+
+    fn repeat(node: Node, min: usize, max: Option<usize>) -> Node {
+        Node::Repeat(Box::new(Repeat::new(node, min, max)))
+    }
+}
+
+/**********/
+/* Errors */
+/**********/
+
+/// Why a pattern string could not be parsed, and where in it the problem is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// What went wrong.
+    pub kind: ParseErrorKind,
+    /// The byte range of the pattern string where it went wrong.
+    pub span: Range<usize>,
+}
+
+/// The ways that parsing a pattern string can fail.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A `(` without a matching `)`.
+    UnclosedGroup,
+    /// A `)` without a matching `(`.
+    UnopenedGroup,
+    /// A `[` without a matching `]`, or a `\p{` without a matching `}`.
+    UnclosedClass,
+    /// A class with nothing in it: `[]`.
+    EmptyClass,
+    /// A class range whose start is after its end, like `[z-a]`.
+    InvalidRange,
+    /// A backslash followed by something that can't be escaped, or by nothing at all.
+    InvalidEscape,
+    /// A `*`, `+`, `?` or `{m,n}` with nothing before it to repeat.
+    NothingToRepeat,
+    /// A malformed `{m,n}`, or one where `m` is greater than `n`.
+    InvalidRepeat,
+    /// A malformed group name in `(?P<name>...)`.
+    InvalidGroupName,
+    /// A `\p{name}` where `name` isn't a unicode general category, script, or property.
+    UnknownClass,
+    /// A repetition that would make the regex too large, like `(a{1000}){1000}`.
+    TooLarge,
+    /// Valid regex syntax, that this library doesn't support.
+    Unsupported(&'static str),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseErrorKind::*;
+
+        match &self.kind {
+            UnclosedGroup => write!(f, "unclosed group")?,
+            UnopenedGroup => write!(f, "unopened group")?,
+            UnclosedClass => write!(f, "unclosed character class")?,
+            EmptyClass => write!(f, "empty character class")?,
+            InvalidRange => write!(f, "invalid character class range")?,
+            InvalidEscape => write!(f, "invalid escape")?,
+            NothingToRepeat => write!(f, "repetition operator with nothing to repeat")?,
+            InvalidRepeat => write!(f, "invalid repetition")?,
+            InvalidGroupName => write!(f, "invalid group name")?,
+            UnknownClass => write!(f, "unknown unicode class")?,
+            TooLarge => write!(f, "pattern too large after expanding repetitions")?,
+            Unsupported(what) => write!(f, "unsupported: {}", what)?,
+        }
+        write!(f, " at {}..{}", self.span.start, self.span.end)
+    }
+}
+
+impl Error for ParseError {}
+
+/**********/
+/* Parser */
+/**********/
+
+struct Parser<'p> {
+    pattern: &'p str,
+    pos: usize,
+    groups: usize,
+    flags: Flags,
+}
+
+/// The flags set by `(?im)`, which last until the end of the enclosing group.
+#[derive(Clone, Copy, Default)]
+struct Flags {
+    case_insensitive: bool,
+    multi_line: bool,
+}
+
+impl<'p> Parser<'p> {
+    fn peek(&self) -> Option<char> {
+        self.pattern[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        if self.pattern[self.pos..].starts_with(prefix) {
+            self.pos += prefix.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, kind: ParseErrorKind, start: usize) -> ParseError {
+        ParseError {
+            kind,
+            span: start..self.pos,
+        }
+    }
+
+    /// An error about the char at the current position.
+    fn error_here(&self, kind: ParseErrorKind) -> ParseError {
+        let len = self.peek().map(char::len_utf8).unwrap_or(0);
+        ParseError {
+            kind,
+            span: self.pos..self.pos + len,
+        }
+    }
+
+    // alt := seq ('|' seq)*
+    fn parse_alt(&mut self) -> Result<Node, ParseError> {
+        let mut node = self.parse_seq()?;
+        while self.eat("|") {
+            node = Node::alt(node, self.parse_seq()?);
+        }
+        Ok(node)
+    }
+
+    // seq := repeat*
+    fn parse_seq(&mut self) -> Result<Node, ParseError> {
+        let mut nodes = vec![];
+        while let Some(ch) = self.peek() {
+            if ch == '|' || ch == ')' {
+                break;
+            }
+            let node = self.parse_repeat()?;
+            // Fold case-insensitive literals together, so that e.g. `ss` can match `ß`.
+            if let (Some(Node::Folded(prev)), Node::Folded(next)) = (nodes.last_mut(), &node) {
+                *prev = prev.concat(next);
+                continue;
+            }
+            nodes.push(node);
+        }
+        Ok(match nodes.pop() {
+            None => Node::Empty(Empty::new()),
+            Some(last) => nodes
+                .into_iter()
+                .rev()
+                .fold(last, |tail, node| Node::seq(node, tail)),
+        })
+    }
+
+    // repeat := atom ('*' | '+' | '?' | '{' bounds '}')*
+    fn parse_repeat(&mut self) -> Result<Node, ParseError> {
+        let start = self.pos;
+        let mut node = self.parse_atom()?;
+        loop {
+            let op = self.peek();
+            let (min, max) = match op {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => self.parse_bounds()?,
+                _ => return Ok(node),
+            };
+            if op != Some('{') {
+                self.next();
+            }
+            // Check the size before building the copies of `node`.
+            let size = Syntax::Repeat(Box::new(node.syntax()), min, max).size();
+            if size > syntax::SIZE_LIMIT {
+                return Err(self.error(ParseErrorKind::TooLarge, start));
+            }
+            node = match op {
+                Some('*') => Node::star(node),
+                Some('?') => Node::maybe(node),
+                _ => Node::repeat(node, min, max),
+            };
+        }
+    }
+
+    // bounds := '{' n '}' | '{' n ',' '}' | '{' n ',' n '}'
+    fn parse_bounds(&mut self) -> Result<(usize, Option<usize>), ParseError> {
+        let start = self.pos;
+        self.next();
+        let min = self.parse_number();
+        let max = if self.eat(",") {
+            if self.peek() == Some('}') {
+                None
+            } else {
+                Some(self.parse_number())
+            }
+        } else {
+            Some(min)
+        };
+        let bounds = match (min, max) {
+            (Some(min), None) => Some((min, None)),
+            (Some(min), Some(Some(max))) if min <= max => Some((min, Some(max))),
+            _ => None,
+        };
+        match bounds {
+            Some(bounds) if self.eat("}") => Ok(bounds),
+            _ => {
+                // Report the whole of the malformed repetition.
+                while let Some(ch) = self.next() {
+                    if ch == '}' {
+                        break;
+                    }
+                }
+                Err(self.error(ParseErrorKind::InvalidRepeat, start))
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while let Some('0'..='9') = self.peek() {
+            self.next();
+        }
+        self.pattern[start..self.pos].parse().ok()
+    }
+
+    // atom := group | class | '.' | escape | literal
+    fn parse_atom(&mut self) -> Result<Node, ParseError> {
+        let start = self.pos;
+        match self.next() {
+            None => unreachable!("parse_seq checks for the end"),
+            Some('(') => self.parse_group(start),
+            Some('[') => self.parse_class(start),
+            Some('.') => Ok(Node::Dot(SingleChar::new(Dot(PhantomData)))),
+            Some('\\') => Ok(match self.parse_escape(start)? {
+                Escape::Char(ch) => self.literal(ch),
+                Escape::Class(class) => self.class(class),
+                Escape::Assert(anchor) => Node::Assert(Assert::new(anchor)),
+            }),
+            Some('*') | Some('+') | Some('?') | Some('{') => {
+                Err(self.error(ParseErrorKind::NothingToRepeat, start))
+            }
+            Some('^') if self.flags.multi_line => Ok(Node::Assert(Assert::new(Anchor::LineStart))),
+            Some('^') => Ok(Node::Assert(Assert::new(Anchor::TextStart))),
+            Some('$') if self.flags.multi_line => Ok(Node::Assert(Assert::new(Anchor::LineEnd))),
+            Some('$') => Ok(Node::Assert(Assert::new(Anchor::TextEnd))),
+            Some(ch) => Ok(self.literal(ch)),
+        }
+    }
+
+    fn literal(&self, ch: char) -> Node {
+        if self.flags.case_insensitive {
+            Node::Folded(FoldedLiteral::new(ch.encode_utf8(&mut [0; 4])))
+        } else {
+            Node::Char(SingleChar::new(Char(ch)))
+        }
+    }
+
+    fn class(&self, class: CharSet) -> Node {
+        if self.flags.case_insensitive {
+            Node::Class(SingleChar::new(class.case_fold()))
+        } else {
+            Node::Class(SingleChar::new(class))
+        }
+    }
+
+    // group := '(' ('?:' | '?P<' name '>' | '?<' name '>')? alt ')' | flags
+    fn parse_group(&mut self, start: usize) -> Result<Node, ParseError> {
+        let name = if self.eat("?:") {
+            None
+        } else if self.eat("?P<") || self.eat("?<") {
+            Some(self.parse_group_name()?)
+        } else if self.eat("?") {
+            return self.parse_flags(start);
+        } else {
+            self.groups += 1;
+            Some(self.groups.to_string())
+        };
+        // Flags set inside the group only last until its end.
+        let flags = self.flags;
+        let node = self.parse_alt()?;
+        self.flags = flags;
+        if !self.eat(")") {
+            return Err(self.error(ParseErrorKind::UnclosedGroup, start));
+        }
+        Ok(match name {
+            None => node,
+            Some(name) => Node::Group(Box::new(Group::new(&name, node))),
+        })
+    }
+
+    // flags := '(?' flag* ('-' flag*)? ')' | '(?' flag* ('-' flag*)? ':' alt ')'
+    // flag := 'i' | 'm'
+    fn parse_flags(&mut self, start: usize) -> Result<Node, ParseError> {
+        let mut flags = self.flags;
+        let mut enable = true;
+        loop {
+            let flag_start = self.pos;
+            match self.next() {
+                None => return Err(self.error(ParseErrorKind::UnclosedGroup, start)),
+                Some('i') => flags.case_insensitive = enable,
+                Some('m') => flags.multi_line = enable,
+                Some('-') if enable => enable = false,
+                Some(')') => {
+                    self.flags = flags;
+                    return Ok(Node::Empty(Empty::new()));
+                }
+                Some(':') => {
+                    let outer = self.flags;
+                    self.flags = flags;
+                    let node = self.parse_alt()?;
+                    self.flags = outer;
+                    if !self.eat(")") {
+                        return Err(self.error(ParseErrorKind::UnclosedGroup, start));
+                    }
+                    return Ok(node);
+                }
+                Some(_) => {
+                    return Err(self.error(
+                        ParseErrorKind::Unsupported("flags other than i and m"),
+                        flag_start,
+                    ))
+                }
+            }
+        }
+    }
+
+    fn parse_group_name(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        while let Some(ch) = self.peek() {
+            if ch == '>' {
+                let name = &self.pattern[start..self.pos];
+                self.next();
+                if name.is_empty() {
+                    return Err(self.error(ParseErrorKind::InvalidGroupName, start));
+                }
+                return Ok(name.to_owned());
+            } else if ch.is_alphanumeric() || ch == '_' {
+                self.next();
+            } else {
+                break;
+            }
+        }
+        Err(self.error_here(ParseErrorKind::InvalidGroupName))
+    }
+
+    // class := '[' '^'? (item | item '-' item)+ ']'
+    fn parse_class(&mut self, start: usize) -> Result<Node, ParseError> {
+        let negated = self.eat("^");
+        let mut ranges = vec![];
+        loop {
+            let item_start = self.pos;
+            let min = match self.next() {
+                None => return Err(self.error(ParseErrorKind::UnclosedClass, start)),
+                Some(']') if ranges.is_empty() => {
+                    return Err(self.error(ParseErrorKind::EmptyClass, start))
+                }
+                Some(']') => break,
+                Some('\\') => match self.parse_escape(item_start)? {
+                    Escape::Char(ch) => ch,
+                    Escape::Assert(_) => {
+                        return Err(self.error(ParseErrorKind::InvalidEscape, item_start))
+                    }
+                    Escape::Class(_) if self.at_range_dash() => {
+                        // Report the whole of the range.
+                        self.next();
+                        self.next();
+                        return Err(self.error(ParseErrorKind::InvalidRange, item_start));
+                    }
+                    Escape::Class(class) => {
+                        ranges.extend_from_slice(class.ranges());
+                        continue;
+                    }
+                },
+                Some(ch) => ch,
+            };
+            let max = if self.at_range_dash() {
+                self.next();
+                match self.next() {
+                    None => return Err(self.error(ParseErrorKind::UnclosedClass, start)),
+                    Some('\\') => match self.parse_escape(item_start)? {
+                        Escape::Char(ch) => ch,
+                        Escape::Class(_) => {
+                            return Err(self.error(ParseErrorKind::InvalidRange, item_start))
+                        }
+                        Escape::Assert(_) => {
+                            return Err(self.error(ParseErrorKind::InvalidEscape, item_start))
+                        }
+                    },
+                    Some(ch) => ch,
+                }
+            } else {
+                min
+            };
+            if min > max {
+                return Err(self.error(ParseErrorKind::InvalidRange, item_start));
+            }
+            ranges.push((min, max));
+        }
+        let mut class = CharSet::new(ranges);
+        if self.flags.case_insensitive {
+            class = class.case_fold();
+        }
+        let class = if negated { class.negate() } else { class };
+        Ok(Node::Class(SingleChar::new(class)))
+    }
+
+    // Is the next char a `-` between two ends of a range (rather than at the end of a class)?
+    fn at_range_dash(&self) -> bool {
+        self.peek() == Some('-') && !self.pattern[self.pos..].starts_with("-]")
+    }
+
+    // The char, class of chars, or assertion after a backslash.
+    fn parse_escape(&mut self, start: usize) -> Result<Escape, ParseError> {
+        let letter = self.next();
+        let class = match letter {
+            Some('n') => return Ok(Escape::Char('\n')),
+            Some('r') => return Ok(Escape::Char('\r')),
+            Some('t') => return Ok(Escape::Char('\t')),
+            Some('b') => return Ok(Escape::Assert(Anchor::WordBoundary)),
+            Some('B') => return Ok(Escape::Assert(Anchor::NotWordBoundary)),
+            Some('A') => return Ok(Escape::Assert(Anchor::TextStart)),
+            Some('z') => return Ok(Escape::Assert(Anchor::TextEnd)),
+            Some('x') => return self.parse_hex(start).map(Escape::Char),
+            // The `regex` crate reads these as word start and end assertions.
+            Some('<') | Some('>') => {
+                return Err(self.error(ParseErrorKind::Unsupported("\\< and \\>"), start))
+            }
+            Some(ch) if ch.is_ascii() && !ch.is_ascii_alphanumeric() => {
+                return Ok(Escape::Char(ch))
+            }
+            Some('d') | Some('D') => predicates::digit(),
+            Some('w') | Some('W') => predicates::word(),
+            Some('s') | Some('S') => predicates::space(),
+            Some('p') | Some('P') => {
+                let braced = self.eat("{");
+                let name_start = self.pos;
+                let name = if braced {
+                    while !matches!(self.peek(), Some('}') | None) {
+                        self.next();
+                    }
+                    let name = &self.pattern[name_start..self.pos];
+                    if !self.eat("}") {
+                        return Err(self.error(ParseErrorKind::UnclosedClass, start));
+                    }
+                    name
+                } else {
+                    self.next();
+                    &self.pattern[name_start..self.pos]
+                };
+                match predicates::unicode_class(name) {
+                    Some(class) => class,
+                    None => return Err(self.error(ParseErrorKind::UnknownClass, start)),
+                }
+            }
+            _ => return Err(self.error(ParseErrorKind::InvalidEscape, start)),
+        };
+        // The escaped letter is uppercase iff the class is negated.
+        if letter.is_some_and(|letter| letter.is_ascii_uppercase()) {
+            Ok(Escape::Class(class.negate()))
+        } else {
+            Ok(Escape::Class(class))
+        }
+    }
+}
+
+impl<'p> Parser<'p> {
+    // The char after `\x`: two hex digits, or any number of them in braces.
+    fn parse_hex(&mut self, start: usize) -> Result<char, ParseError> {
+        let braced = self.eat("{");
+        let digits = if braced {
+            let digits_start = self.pos;
+            while !matches!(self.peek(), Some('}') | None) {
+                self.next();
+            }
+            let digits = &self.pattern[digits_start..self.pos];
+            if !self.eat("}") {
+                return Err(self.error(ParseErrorKind::InvalidEscape, start));
+            }
+            digits
+        } else {
+            let digits_start = self.pos;
+            self.next();
+            self.next();
+            &self.pattern[digits_start..self.pos]
+        };
+        let valid = if braced {
+            (1..=8).contains(&digits.len())
+        } else {
+            digits.len() == 2
+        };
+        let code = if valid && digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            u32::from_str_radix(digits, 16).ok()
+        } else {
+            None
+        };
+        code.and_then(std::char::from_u32)
+            .ok_or_else(|| self.error(ParseErrorKind::InvalidEscape, start))
+    }
+}
+
+enum Escape {
+    Char(char),
+    Class(CharSet),
+    Assert(Anchor),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(pattern: &str, matches: &[&str], non_matches: &[&str]) {
+        let mut regex = Pattern::new(pattern).unwrap();
+        let expected = regex::Regex::new(&format!("^(?:{})$", pattern)).unwrap();
+        for input in matches {
+            assert!(
+                expected.is_match(input),
+                "{} should match {}",
+                pattern,
+                input
+            );
+            assert!(regex.is_match(input), "{} should match {}", pattern, input);
+        }
+        for input in non_matches {
+            assert!(
+                !expected.is_match(input),
+                "{} shouldn't match {}",
+                pattern,
+                input
+            );
+            assert!(
+                !regex.is_match(input),
+                "{} shouldn't match {}",
+                pattern,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn parse() {
+        check(
+            r"(0|[1-9][0-9]*)(\.[0-9]*)?",
+            &["0", "10", "3.14", "7.", "0.0"],
+            &["", "01", ".5", "1.2.3", "a"],
+        );
+        check("a|b|", &["a", "b", ""], &["ab", "c"]);
+        check("(?:ab)+c?", &["ab", "ababc"], &["", "abca", "aba"]);
+        check("x{3}", &["xxx"], &["xx", "xxxx"]);
+        check("x{2,}", &["xx", "xxxxx"], &["x"]);
+        check("x{1,3}y{0}", &["x", "xxx"], &["", "xxxx", "xy"]);
+        check(r"[-\]a-c.]*", &["", "-]b.", "ca-"], &["d", "\\"]);
+        check(r"\(\\\)\n", &["(\\)\n"], &["(\\)n"]);
+        check(r"a\/\#\&\~\ b", &["a/#&~ b"], &["a/#&~b"]);
+        check("α.ω", &["αβω", "α.ω"], &["αω"]);
+        check("[^a-z0-9_]+", &["-+!", "Ω"], &["", "-a"]);
+        check(r"\d+\s\w*", &["42 ab_c", "٣ é"], &["4 -", "a b"]);
+        check(r"[\d\sx-z]*\D", &["1 y2 a"], &["12"]);
+        check(r"\p{Greek}+\PL\pN", &["λό 7"], &["λό a7", "abc 7"]);
+        check("(?i)abc", &["abc", "AbC"], &["abd"]);
+        check("a(?i)bc|d", &["aBC", "aBc", "D"], &["ABC"]);
+        check("(?i:a)b", &["ab", "Ab"], &["AB"]);
+        check("(?i)a(?-i)b", &["Ab"], &["AB"]);
+        check("(?i)[a-c]+[^x]", &["aBcY"], &["abX"]);
+        check("(?i)[r-t][^K]", &["ſ-", "S+"], &["sk", "sK", "u-"]);
+        check("(?i)(a)B", &["Ab"], &["aa"]);
+        check("(?i)s+", &["sSſ"], &["ß"]);
+        check(r"^a\b.*\Bc$", &["a cc", "a-bc"], &["ac", "a c"]);
+        check(r"(?m)a$\n^b", &["a\nb"], &["a\n\nb"]);
+        check(r"(?im)^A$|\Ax\z", &["a", "x"], &["ax"]);
+        check(r"\x41[\x{3B1}-\x{3C9}]", &["Aα", "Aω"], &["A", "aα"]);
+
+        let mut date = Pattern::new(r"(?P<year>[0-9]{4})-([0-9]{2})").unwrap();
+        let caps = date.captures("2021-04").unwrap();
+        assert_eq!(caps.get("year").unwrap().bytes(), 0..4);
+        assert_eq!(caps.get("1").unwrap().bytes(), 5..7);
+
+        // Unlike the `regex` crate, literals use full case folding.
+        let mut street = Pattern::new("(?i)straße").unwrap();
+        assert!(street.is_match("STRASSE"));
+        assert!(Pattern::new("(?i)STRASSE").unwrap().is_match("Straße"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        use ParseErrorKind::*;
+
+        let error = |pattern| Pattern::new(pattern).err().unwrap();
+        assert_eq!(
+            error("ab(cd"),
+            ParseError {
+                kind: UnclosedGroup,
+                span: 2..5
+            }
+        );
+        assert_eq!(
+            error("ab)"),
+            ParseError {
+                kind: UnopenedGroup,
+                span: 2..3
+            }
+        );
+        assert_eq!(
+            error("a[bc"),
+            ParseError {
+                kind: UnclosedClass,
+                span: 1..4
+            }
+        );
+        assert_eq!(
+            error("[]"),
+            ParseError {
+                kind: EmptyClass,
+                span: 0..2
+            }
+        );
+        assert_eq!(
+            error("x[z-a]"),
+            ParseError {
+                kind: InvalidRange,
+                span: 2..5
+            }
+        );
+        assert_eq!(
+            error(r"a\q"),
+            ParseError {
+                kind: InvalidEscape,
+                span: 1..3
+            }
+        );
+        assert_eq!(
+            error(r"\<a"),
+            ParseError {
+                kind: Unsupported("\\< and \\>"),
+                span: 0..2
+            }
+        );
+        assert_eq!(
+            error("a|*"),
+            ParseError {
+                kind: NothingToRepeat,
+                span: 2..3
+            }
+        );
+        assert_eq!(
+            error("a{3,1}"),
+            ParseError {
+                kind: InvalidRepeat,
+                span: 1..6
+            }
+        );
+        assert_eq!(
+            error("a{x}"),
+            ParseError {
+                kind: InvalidRepeat,
+                span: 1..4
+            }
+        );
+        assert_eq!(
+            error(r"[\d-z]"),
+            ParseError {
+                kind: InvalidRange,
+                span: 1..5
+            }
+        );
+        assert_eq!(
+            error(r"a\p{Klingon}"),
+            ParseError {
+                kind: UnknownClass,
+                span: 1..12
+            }
+        );
+        for pattern in [r"\p{", r"\P{", r"\p{é", r"\p{Lu"] {
+            assert_eq!(
+                error(pattern),
+                ParseError {
+                    kind: UnclosedClass,
+                    span: 0..pattern.len()
+                }
+            );
+        }
+        assert_eq!(
+            error(r"\p"),
+            ParseError {
+                kind: UnknownClass,
+                span: 0..2
+            }
+        );
+        assert_eq!(
+            error("(?P<a b>)"),
+            ParseError {
+                kind: InvalidGroupName,
+                span: 5..6
+            }
+        );
+        assert_eq!(
+            error(r"[a\b]"),
+            ParseError {
+                kind: InvalidEscape,
+                span: 2..4
+            }
+        );
+        assert_eq!(
+            error(r"a\x{D800}b"),
+            ParseError {
+                kind: InvalidEscape,
+                span: 1..9
+            }
+        );
+        assert_eq!(
+            error(r"\x4"),
+            ParseError {
+                kind: InvalidEscape,
+                span: 0..3
+            }
+        );
+        assert_eq!(
+            error("x(a{1000}){1000}"),
+            ParseError {
+                kind: TooLarge,
+                span: 1..16
+            }
+        );
+        assert_eq!(
+            error("((a{1000}){1000}){100}"),
+            ParseError {
+                kind: TooLarge,
+                span: 1..16
+            }
+        );
+        assert!(Pattern::new("(a{100}){100}[0-9]{3,5}").is_ok());
+        assert_eq!(
+            error("(?ix)").to_string(),
+            "unsupported: flags other than i and m at 3..4"
+        );
+    }
+}
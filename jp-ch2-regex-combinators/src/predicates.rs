@@ -0,0 +1,351 @@
+use super::{Char, CharRange, Dot, Predicate, Symbol};
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, HirKind, Literal};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Match any char.
+pub fn any<T: Symbol>() -> impl Predicate<T> {
+    Dot(PhantomData)
+}
+
+/// Match only `ch`.
+pub fn is<T: Symbol + Eq>(ch: T) -> impl Predicate<T> {
+    Char(ch)
+}
+
+/// Match a char in the given range (in unicode code point order), inclusive on both ends.
+pub fn range<T: Symbol + Ord>(min_ch: T, max_ch: T) -> impl Predicate<T> {
+    CharRange(min_ch, max_ch)
+}
+
+/// Match a char iff both `left` and `right` match it.
+pub fn and<T: Symbol>(left: impl Predicate<T>, right: impl Predicate<T>) -> impl Predicate<T> {
+    And(left, right)
+}
+
+/// Match a char iff either `left` or `right` (or both) match it.
+pub fn or<T: Symbol>(left: impl Predicate<T>, right: impl Predicate<T>) -> impl Predicate<T> {
+    Or(left, right)
+}
+
+/// Match a char iff `predicate` does not match it.
+pub fn not<T: Symbol>(predicate: impl Predicate<T>) -> impl Predicate<T> {
+    Not(predicate)
+}
+
+/// Match a char in any of the given (inclusive) ranges, like `[a-z0-9_]`.
+pub fn set(ranges: &[(char, char)]) -> CharSet {
+    CharSet::new(ranges.iter().copied())
+}
+
+/// Match a char in none of the given (inclusive) ranges, like `[^a-z0-9_]`.
+pub fn none_of(ranges: &[(char, char)]) -> CharSet {
+    set(ranges).negate()
+}
+
+/// Match a unicode decimal digit, like `\d`.
+pub fn digit() -> CharSet {
+    perl_class('d')
+}
+
+/// Match a unicode "word" char, like `\w`.
+pub fn word() -> CharSet {
+    perl_class('w')
+}
+
+/// Match a unicode whitespace char, like `\s`.
+pub fn space() -> CharSet {
+    perl_class('s')
+}
+
+/// Match a char in the unicode general category with the given name or abbreviation, like
+/// `"Uppercase_Letter"`, `"Lu"`, or `"L"`. Returns `None` if there is no such category.
+pub fn category(name: &str) -> Option<CharSet> {
+    unicode_class(&format!("gc={}", name))
+}
+
+/// Match a char in the unicode script with the given name or abbreviation, like `"Greek"` or
+/// `"Grek"`. Returns `None` if there is no such script.
+pub fn script(name: &str) -> Option<CharSet> {
+    unicode_class(&format!("sc={}", name))
+}
+
+fn perl_class(name: char) -> CharSet {
+    from_syntax(&format!("\\{}", name)).expect("perl classes are always defined")
+}
+
+/// The unicode class that would be written `\p{name}` in regex syntax: a general category,
+/// script, or binary property. Returns `None` if there is no such class.
+pub(crate) fn unicode_class(name: &str) -> Option<CharSet> {
+    if name.contains(['{', '}']) {
+        return None;
+    }
+    from_syntax(&format!("\\p{{{}}}", name))
+}
+
+// Look up the unicode tables by asking `regex-syntax` to parse a one-class regex.
+fn from_syntax(class: &str) -> Option<CharSet> {
+    let hir = regex_syntax::Parser::new().parse(class).ok()?;
+    match hir.kind() {
+        HirKind::Class(Class::Unicode(class)) => Some(CharSet::new(
+            class
+                .ranges()
+                .iter()
+                .map(|range| (range.start(), range.end())),
+        )),
+        HirKind::Literal(Literal::Unicode(ch)) => Some(CharSet::new(vec![(*ch, *ch)])),
+        _ => None,
+    }
+}
+
+/*************/
+/* Char Sets */
+/*************/
+
+/// A set of chars, stored as sorted, non-overlapping ranges. Unlike combining `range`s with
+/// `or`, this takes `O(log n)` time to test a char against `n` ranges.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CharSet {
+    ranges: Arc<[(char, char)]>,
+}
+
+impl CharSet {
+    /// The set of chars in any of the given (inclusive) ranges. Ranges whose start is after their
+    /// end are empty.
+    pub fn new(ranges: impl IntoIterator<Item = (char, char)>) -> CharSet {
+        let mut ranges = ranges
+            .into_iter()
+            .filter(|(min, max)| min <= max)
+            .collect::<Vec<_>>();
+        ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(ranges.len());
+        for (min, max) in ranges {
+            match merged.last_mut() {
+                Some(last) if next_char(last.1).is_none_or(|next| next >= min) => {
+                    last.1 = last.1.max(max);
+                }
+                _ => merged.push((min, max)),
+            }
+        }
+        CharSet {
+            ranges: merged.into(),
+        }
+    }
+
+    /// The sorted, non-overlapping, non-adjacent ranges of chars in this set.
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.ranges
+    }
+
+    /// The set of all chars that are not in this set.
+    pub fn negate(&self) -> CharSet {
+        let mut ranges = vec![];
+        let mut next = Some('\0');
+        for &(min, max) in self.ranges.iter() {
+            if let Some(next) = next {
+                if next < min {
+                    ranges.push((next, prev_char(min)));
+                }
+            }
+            next = next_char(max);
+        }
+        if let Some(next) = next {
+            ranges.push((next, char::MAX));
+        }
+        CharSet {
+            ranges: ranges.into(),
+        }
+    }
+
+    /// The set of chars in either this set or `other`.
+    pub fn union(&self, other: &CharSet) -> CharSet {
+        CharSet::new(self.ranges.iter().chain(other.ranges.iter()).copied())
+    }
+
+    /// The set of chars that are equal to some char in this set, up to (simple) case folding. For
+    /// example, the case folding of `[Z-a]` also contains `z` and `A`.
+    pub fn case_fold(&self) -> CharSet {
+        let mut class = ClassUnicode::new(
+            self.ranges
+                .iter()
+                .map(|&(min, max)| ClassUnicodeRange::new(min, max)),
+        );
+        class.case_fold_simple();
+        CharSet::new(
+            class
+                .ranges()
+                .iter()
+                .map(|range| (range.start(), range.end())),
+        )
+    }
+}
+
+impl Predicate for CharSet {
+    fn matches(&self, ch: char) -> bool {
+        self.ranges
+            .binary_search_by(|&(min, max)| {
+                if max < ch {
+                    std::cmp::Ordering::Less
+                } else if min > ch {
+                    std::cmp::Ordering::Greater
+                } else {
+                    std::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+
+    fn is_universal(&self) -> bool {
+        self.ranges[..] == [('\0', char::MAX)]
+    }
+
+    fn char_set(&self) -> Option<CharSet> {
+        Some(self.clone())
+    }
+}
+
+// The next char in code point order, skipping over the surrogates.
+fn next_char(ch: char) -> Option<char> {
+    match ch {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => std::char::from_u32(ch as u32 + 1),
+    }
+}
+
+// The previous char in code point order, skipping over the surrogates. `ch` must not be '\0'.
+fn prev_char(ch: char) -> char {
+    match ch {
+        '\u{E000}' => '\u{D7FF}',
+        _ => std::char::from_u32(ch as u32 - 1).unwrap(),
+    }
+}
+
+/*************************/
+/* Predicate Combinators */
+/*************************/
+
+#[derive(Clone)]
+struct And<P, Q>(P, Q);
+
+impl<T: Symbol, P: Predicate<T>, Q: Predicate<T>> Predicate<T> for And<P, Q> {
+    fn matches(&self, ch: T) -> bool {
+        self.0.matches(ch) && self.1.matches(ch)
+    }
+
+    fn is_universal(&self) -> bool {
+        self.0.is_universal() && self.1.is_universal()
+    }
+
+    fn char_set(&self) -> Option<CharSet> {
+        let (left, right) = (self.0.char_set()?, self.1.char_set()?);
+        Some(left.negate().union(&right.negate()).negate())
+    }
+}
+
+#[derive(Clone)]
+struct Or<P, Q>(P, Q);
+
+impl<T: Symbol, P: Predicate<T>, Q: Predicate<T>> Predicate<T> for Or<P, Q> {
+    fn matches(&self, ch: T) -> bool {
+        self.0.matches(ch) || self.1.matches(ch)
+    }
+
+    fn is_universal(&self) -> bool {
+        self.0.is_universal() || self.1.is_universal()
+    }
+
+    fn char_set(&self) -> Option<CharSet> {
+        Some(self.0.char_set()?.union(&self.1.char_set()?))
+    }
+}
+
+#[derive(Clone)]
+struct Not<P>(P);
+
+impl<T: Symbol, P: Predicate<T>> Predicate<T> for Not<P> {
+    fn matches(&self, ch: T) -> bool {
+        !self.0.matches(ch)
+    }
+
+    fn char_set(&self) -> Option<CharSet> {
+        Some(self.0.char_set()?.negate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Regex;
+
+    #[test]
+    fn predicates() {
+        let ident_char = or(range('a', 'z'), or(range('0', '9'), is('_')));
+        let mut ident = seq(
+            one_of(and(ident_char.clone(), not(range('0', '9')))),
+            star(one_of(ident_char)),
+        );
+        assert!(ident.is_match("snake_case_2"));
+        assert!(ident.is_match("_"));
+        assert!(!ident.is_match("2fast"));
+        assert!(!ident.is_match("camelCase"));
+
+        let mut hex = plus(one_of(|ch: char| ch.is_ascii_hexdigit()));
+        assert!(hex.is_match("C0ffee"));
+        assert!(!hex.is_match("tea"));
+
+        let mut anything = star(one_of(any()));
+        assert!(anything.is_match("(╯°□°)╯︵ ┻━┻"));
+    }
+
+    #[test]
+    fn char_sets() {
+        let lower = set(&[('m', 'z'), ('a', 'f'), ('e', 'n'), ('_', '_')]);
+        assert_eq!(lower.ranges(), &[('_', '_'), ('a', 'z')]);
+        let not_lower = none_of(&[('a', 'z'), ('_', '_')]);
+        assert_eq!(
+            not_lower.ranges(),
+            &[('\0', '^'), ('`', '`'), ('{', char::MAX)]
+        );
+        assert_eq!(not_lower.negate(), lower);
+        assert_eq!(lower.union(&not_lower).ranges(), &[('\0', char::MAX)]);
+        assert_eq!(set(&[]).negate().ranges(), &[('\0', char::MAX)]);
+        // Adjacent across the surrogate gap.
+        assert_eq!(
+            set(&[('\u{D000}', '\u{D7FF}'), ('\u{E000}', '\u{E0FF}')]).ranges(),
+            &[('\u{D000}', '\u{E0FF}')]
+        );
+
+        let mut not_ident = plus(one_of(none_of(&[('a', 'z'), ('0', '9'), ('_', '_')])));
+        assert!(not_ident.is_match("-+ !"));
+        assert!(!not_ident.is_match("-+a!"));
+    }
+
+    #[test]
+    fn unicode_classes() {
+        assert!(digit().matches('7'));
+        assert!(digit().matches('٣'));
+        assert!(!digit().matches('x'));
+        assert!(word().matches('é'));
+        assert!(!word().matches('-'));
+        assert!(space().matches('\u{2003}'));
+        assert!(!space().matches('_'));
+
+        let upper = category("Lu").unwrap();
+        assert_eq!(category("Uppercase_Letter"), Some(upper.clone()));
+        assert!(upper.matches('Ж'));
+        assert!(!upper.matches('ж'));
+        assert!(category("Greek").is_none());
+
+        let greek = script("Greek").unwrap();
+        assert!(greek.matches('λ'));
+        assert!(!greek.matches('l'));
+        assert!(script("Lu").is_none());
+        assert!(script("Klingon").is_none());
+
+        let mut greek_word = plus(one_of(greek));
+        assert!(greek_word.is_match("λόγος"));
+        assert!(!greek_word.is_match("logos"));
+    }
+}
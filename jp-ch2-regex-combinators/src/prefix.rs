@@ -0,0 +1,292 @@
+use super::explore::{Alphabet, Explorer};
+use super::predicates::{self, CharSet};
+use super::{NoSyntaxError, Regex};
+use std::collections::HashSet;
+
+/// Whether a string could be the start of a match, as returned by `Regex.check_prefix(&str)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefixStatus {
+    /// The regex matches the string (and might match some longer strings too).
+    Complete,
+    /// The regex doesn't match the string, but matches some longer string that starts with it.
+    ///
+    /// This is only certain if the regex has syntax (see `Regex.syntax()`) and supports snapshots
+    /// (see `Regex.write_state`), and there aren't too many states to explore from the string.
+    /// Otherwise, it means that the regex hasn't died yet, so a longer match hasn't been ruled out.
+    Incomplete,
+    /// The regex doesn't match the string, or any longer string that starts with it.
+    Impossible,
+}
+
+/// For regexes that use the context, the possibilities for the char after a position that
+/// anchors can tell apart: the end of the input, a newline, a word char, and any other char.
+pub(crate) const NEXT: [Option<char>; 4] = [None, Some('\n'), Some('a'), Some(' ')];
+
+/// The most states to explore after a prefix to tell whether a longer match is possible.
+const STATE_LIMIT: usize = 10_000;
+
+/// A regex that has been run over all of a string except its last step (`start()` if the string
+/// is empty, or else advancing over its last char), which for regexes that use the context can't
+/// be taken until the char after it is known.
+struct Pending<'r, R: Regex> {
+    regex: &'r mut R,
+    last: Option<char>,
+    context: bool,
+}
+
+impl<'r, R: Regex> Pending<'r, R> {
+    /// Run `regex` over `input`, except for the last step. If the status of `input` is already
+    /// known (because the regex died or saturated), return that instead.
+    fn new(regex: &'r mut R, input: &str) -> Result<Pending<'r, R>, PrefixStatus> {
+        let context = regex.uses_context();
+        regex.initialize();
+        let mut pending = Pending {
+            regex,
+            last: None,
+            context,
+        };
+        for ch in input.chars() {
+            pending.step(Some(ch));
+            if pending.regex.is_dead() {
+                return Err(PrefixStatus::Impossible);
+            }
+            if pending.regex.is_saturated() {
+                return Err(PrefixStatus::Complete);
+            }
+            pending.last = Some(ch);
+        }
+        Ok(pending)
+    }
+
+    /// Like `new`, for a regex that has already been run up to `last`.
+    fn new_after(regex: &'r mut R, last: char, context: bool) -> Result<Self, PrefixStatus> {
+        if regex.is_dead() {
+            Err(PrefixStatus::Impossible)
+        } else if regex.is_saturated() {
+            Err(PrefixStatus::Complete)
+        } else {
+            Ok(Pending {
+                regex,
+                last: Some(last),
+                context,
+            })
+        }
+    }
+
+    /// Take the last step, given the char after it.
+    fn step(&mut self, next: Option<char>) {
+        if self.context {
+            self.regex.context(self.last, next);
+        }
+        match self.last {
+            None => self.regex.start(),
+            Some(ch) => self.regex.advance(ch),
+        }
+    }
+
+    /// A copy of the regex after the last step, given the char after it.
+    fn stepped(&self, next: Option<char>) -> R {
+        let mut regex = self.regex.clone();
+        let mut pending = Pending {
+            regex: &mut regex,
+            last: self.last,
+            context: self.context,
+        };
+        pending.step(next);
+        regex
+    }
+
+    /// Explore the states after the last step, if the regex has syntax.
+    fn explorer(&self) -> Option<Explorer<R>> {
+        let alphabet = Alphabet::new(&[self.regex.syntax()]).ok()?;
+        Some(Explorer::new(self.regex.clone(), self.last, alphabet))
+    }
+
+    fn status(&self) -> PrefixStatus {
+        let rough = self.rough_status();
+        if rough != PrefixStatus::Incomplete {
+            return rough;
+        }
+        // The regex hasn't died, but it might never match.
+        match self
+            .explorer()
+            .and_then(|mut explorer| can_match(&mut explorer, 0))
+        {
+            Some(false) => PrefixStatus::Impossible,
+            _ => rough,
+        }
+    }
+
+    /// The status without exploring, when all that's known is whether the regex has died.
+    fn rough_status(&self) -> PrefixStatus {
+        let end = self.stepped(None);
+        if end.accepts() {
+            PrefixStatus::Complete
+        } else if !end.is_dead()
+            || (self.context && NEXT[1..].iter().any(|&next| !self.stepped(next).is_dead()))
+        {
+            PrefixStatus::Incomplete
+        } else {
+            PrefixStatus::Impossible
+        }
+    }
+}
+
+/// Can state `from` of `explorer` reach a match? `None` if that can't be told, because a state on
+/// the way has no snapshot, or there are more than `STATE_LIMIT` of them.
+fn can_match<R: Regex>(explorer: &mut Explorer<R>, from: usize) -> Option<bool> {
+    if explorer.accepts(from) {
+        return Some(true);
+    }
+    let mut seen = HashSet::new();
+    let mut stack = vec![from];
+    while let Some(id) = stack.pop() {
+        for i in 0..explorer.alphabet.len() {
+            if !explorer.has_snapshots() || explorer.len() > STATE_LIMIT {
+                return None;
+            }
+            match explorer.child(id, i) {
+                Some(child) if seen.insert(child) => {
+                    if explorer.accepts(child) {
+                        return Some(true);
+                    }
+                    stack.push(child);
+                }
+                _ => (),
+            }
+        }
+    }
+    explorer.has_snapshots().then_some(false)
+}
+
+pub(crate) fn check_prefix<R: Regex>(regex: &mut R, input: &str) -> PrefixStatus {
+    match Pending::new(regex, input) {
+        Ok(pending) => pending.status(),
+        Err(status) => status,
+    }
+}
+
+pub(crate) fn next_chars<R: Regex>(regex: &mut R, input: &str) -> Result<CharSet, NoSyntaxError> {
+    let alphabet = Alphabet::new(&[regex.syntax()])?;
+    let pending = match Pending::new(regex, input) {
+        Ok(pending) => pending,
+        Err(PrefixStatus::Complete) => return Ok(predicates::none_of(&[])),
+        Err(_) => return Ok(predicates::set(&[])),
+    };
+    // The chars whose ranges lead to a state that can reach a match.
+    let mut explorer = Explorer::new(pending.regex.clone(), pending.last, alphabet);
+    let mut ranges = Some(vec![]);
+    for i in 0..explorer.alphabet.len() {
+        let live = match explorer.child(0, i) {
+            Some(child) => can_match(&mut explorer, child),
+            None => Some(false),
+        };
+        match (live, &mut ranges) {
+            (Some(true), Some(ranges)) => ranges.push(explorer.alphabet.ranges[i]),
+            (Some(false), _) => (),
+            _ => ranges = None,
+        }
+    }
+    if let Some(ranges) = ranges {
+        return Ok(CharSet::new(ranges));
+    }
+    let alphabet = &explorer.alphabet;
+    let mut ranges = vec![];
+    for (&range, &ch) in alphabet.ranges.iter().zip(&alphabet.representatives) {
+        // Every char in the range has the same effect, so try one. Exploring after it would fail
+        // for the same reason as exploring before it did.
+        let mut regex = pending.stepped(Some(ch));
+        let alive = match Pending::new_after(&mut regex, ch, pending.context) {
+            Ok(pending) => pending.rough_status() != PrefixStatus::Impossible,
+            Err(status) => status != PrefixStatus::Impossible,
+        };
+        if alive {
+            ranges.push(range);
+        }
+    }
+    Ok(CharSet::new(ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::{Pattern, Predicate};
+    use PrefixStatus::*;
+
+    #[test]
+    fn prefixes() {
+        let mut phone = Pattern::new(r"^\d{3}-\d{4}$").unwrap();
+        assert_eq!(phone.check_prefix(""), Incomplete);
+        assert_eq!(phone.check_prefix("55"), Incomplete);
+        assert_eq!(phone.check_prefix("555-"), Incomplete);
+        assert_eq!(phone.check_prefix("555-0123"), Complete);
+        assert_eq!(phone.check_prefix("555-01234"), Impossible);
+        assert_eq!(phone.check_prefix("55a"), Impossible);
+        assert_eq!(
+            phone.next_chars("555").unwrap(),
+            predicates::set(&[('-', '-')])
+        );
+        assert_eq!(phone.next_chars("555-0123").unwrap(), predicates::set(&[]));
+        assert_eq!(phone.next_chars("5").unwrap(), predicates::digit());
+
+        let mut word = seq(plus(char_range('a', 'z')), word_boundary());
+        assert_eq!(word.check_prefix("ab"), Complete);
+        assert_eq!(word.check_prefix("ab "), Impossible);
+        assert_eq!(
+            word.next_chars("ab").unwrap(),
+            predicates::set(&[('a', 'z')])
+        );
+
+        // Regexes that haven't died can still be impossible to complete.
+        let mut never = intersect(seq(achar('a'), achar('b')), seq(achar('a'), achar('c')));
+        assert_eq!(never.check_prefix("a"), Impossible);
+        assert_eq!(never.check_prefix(""), Impossible);
+        assert_eq!(never.next_chars("").unwrap(), predicates::set(&[]));
+        let mut either = intersect(star(dot()), Pattern::new("ab|ac").unwrap());
+        assert_eq!(either.check_prefix("a"), Incomplete);
+        assert_eq!(
+            either.next_chars("a").unwrap(),
+            predicates::set(&[('b', 'c')])
+        );
+
+        let mut anything = seq(achar('x'), star(dot()));
+        assert_eq!(anything.check_prefix("xyz"), Complete);
+        assert_eq!(anything.next_chars("x").unwrap(), predicates::none_of(&[]));
+
+        // Regexes without syntax could tell any chars apart, so only their prefixes are checked.
+        let mut opaque = seq(one_of(|ch: char| ch.is_ascii_digit()), achar('x'));
+        assert_eq!(opaque.check_prefix("1"), Incomplete);
+        assert_eq!(
+            opaque.next_chars(""),
+            Err(NoSyntaxError {
+                part: "{{closure}}".to_owned()
+            })
+        );
+
+        // Each char in the result keeps the regex alive, and no other char does.
+        let patterns = [
+            r"(?i)straße|k[a-c]",
+            r"(?m)a$\n?\b",
+            r"[^a-c]x|\Bz",
+            r"(?i:ı)",
+        ];
+        for pattern in &patterns {
+            let mut regex = Pattern::new(pattern).unwrap();
+            for input in ["", "k", "s", "a", "a\n", "dx", "I"] {
+                let chars = regex.next_chars(input).unwrap();
+                for ch in ('\0'..'\u{2000}').chain(['ſ', 'K', 'ẞ', 'İ']) {
+                    let status = regex.check_prefix(&format!("{}{}", input, ch));
+                    assert_eq!(
+                        chars.matches(ch),
+                        status != Impossible,
+                        "{} after {:?}: {:?}",
+                        pattern,
+                        input,
+                        ch
+                    );
+                }
+            }
+        }
+    }
+}
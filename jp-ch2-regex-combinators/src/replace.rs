@@ -0,0 +1,203 @@
+use super::{Captures, FindIter, Regex};
+use std::borrow::Cow;
+
+/// What to replace each match with, in `Regex.replace` and `Regex.replace_all`.
+///
+/// This is implemented by strings, which can refer to groups as `$name` (see `Captures.expand`),
+/// by `NoExpand`, for strings to be used as they are, and by closures that take the `Captures` of
+/// the match and return the replacement. Since `Captures` only hold positions, a closure that
+/// needs the matched text should slice the input with them.
+pub trait Replacer {
+    /// Append the replacement for the match `caps` in `input` to `dst`.
+    fn replace_append(&mut self, input: &str, caps: &Captures, dst: &mut String);
+
+    /// Does the replacement look at the groups in `caps`, or just the span of the match? Matches
+    /// can sometimes be found sooner without their groups. It is always safe to return true.
+    fn uses_groups(&self) -> bool {
+        true
+    }
+}
+
+impl Replacer for &str {
+    fn replace_append(&mut self, input: &str, caps: &Captures, dst: &mut String) {
+        caps.expand(input, self, dst);
+    }
+
+    fn uses_groups(&self) -> bool {
+        self.contains('$')
+    }
+}
+
+impl Replacer for &String {
+    fn replace_append(&mut self, input: &str, caps: &Captures, dst: &mut String) {
+        caps.expand(input, self, dst);
+    }
+
+    fn uses_groups(&self) -> bool {
+        self.contains('$')
+    }
+}
+
+impl Replacer for String {
+    fn replace_append(&mut self, input: &str, caps: &Captures, dst: &mut String) {
+        caps.expand(input, self, dst);
+    }
+
+    fn uses_groups(&self) -> bool {
+        self.contains('$')
+    }
+}
+
+impl<F: FnMut(&Captures) -> T, T: AsRef<str>> Replacer for F {
+    fn replace_append(&mut self, _input: &str, caps: &Captures, dst: &mut String) {
+        dst.push_str(self(caps).as_ref());
+    }
+}
+
+/// A replacement string that is used as it is, without expanding `$name`s.
+#[derive(Clone, Copy, Debug)]
+pub struct NoExpand<'s>(pub &'s str);
+
+impl Replacer for NoExpand<'_> {
+    fn replace_append(&mut self, _input: &str, _caps: &Captures, dst: &mut String) {
+        dst.push_str(self.0);
+    }
+
+    fn uses_groups(&self) -> bool {
+        false
+    }
+}
+
+/// Replace the first `limit` matches of `regex` in `input`, or all of them if `limit` is 0. This
+/// borrows the input if there are no matches.
+pub(crate) fn replacen<'t, R: Regex>(
+    regex: &mut R,
+    input: &'t str,
+    limit: usize,
+    mut replacer: impl Replacer,
+) -> Cow<'t, str> {
+    let groups = replacer.uses_groups();
+    let mut matches = FindIter::new(regex, input);
+    let mut output = String::new();
+    let mut last_end = 0;
+    let mut count = 0;
+    while limit == 0 || count < limit {
+        let caps = match matches.next_captures(groups) {
+            Some(caps) => caps,
+            None => break,
+        };
+        let range = caps.span().bytes();
+        output.push_str(&input[last_end..range.start]);
+        replacer.replace_append(input, &caps, &mut output);
+        last_end = range.end;
+        count += 1;
+    }
+    if count == 0 {
+        return Cow::Borrowed(input);
+    }
+    output.push_str(&input[last_end..]);
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MatchKind, Pattern};
+
+    #[test]
+    fn replace_and_split() {
+        let inputs = [
+            "",
+            "2021-04 and 1999-12",
+            "no dates",
+            "2021-04-2021-05",
+            "ab, cd,ef ,, g",
+            "aXbXXc",
+        ];
+        let check_replace = |pattern: &str, replacement: &str| {
+            let mut regex = Pattern::new(pattern).unwrap();
+            let expected = regex::Regex::new(pattern).unwrap();
+            for input in &inputs {
+                assert_eq!(
+                    regex.replace(input, replacement),
+                    expected.replace(input, replacement),
+                    "{} -> {} on {:?}",
+                    pattern,
+                    replacement,
+                    input
+                );
+                assert_eq!(
+                    regex.replace_all(input, replacement),
+                    expected.replace_all(input, replacement),
+                    "{} -> {} on {:?}",
+                    pattern,
+                    replacement,
+                    input
+                );
+            }
+        };
+        // (Named groups aren't numbered, unlike in the `regex` crate, so don't mix the two.)
+        check_replace(r"(?P<y>[0-9]{4})-(?P<m>[0-9]{2})", "$m/$y");
+        check_replace(r"([0-9]{4})-([0-9]{2})", "${1}x$1x $$0 $0 $02 $");
+        check_replace(r"(?P<y>[0-9]{4})-([0-9]{2})", "[${y}] $missing ${unclosed");
+        check_replace(r"(a)|(b)", "<$1|$2>");
+        check_replace("X*", "-");
+        check_replace(r"\s*,\s*", ";");
+
+        let check_split = |pattern: &str| {
+            let mut regex = Pattern::new(pattern).unwrap();
+            let expected = regex::Regex::new(pattern).unwrap();
+            for input in &inputs {
+                assert_eq!(
+                    regex.split(input).collect::<Vec<_>>(),
+                    expected.split(input).collect::<Vec<_>>(),
+                    "{} on {:?}",
+                    pattern,
+                    input
+                );
+                for limit in 0..5 {
+                    assert_eq!(
+                        regex.splitn(input, limit).collect::<Vec<_>>(),
+                        expected.splitn(input, limit).collect::<Vec<_>>(),
+                        "{} on {:?}, limit {}",
+                        pattern,
+                        input,
+                        limit
+                    );
+                }
+            }
+        };
+        check_split(r"\s*,\s*");
+        check_split("X");
+        check_split("X*");
+        check_split("-");
+        check_split("");
+
+        // Matches are leftmost-longest, not leftmost-first as in the `regex` crate.
+        let mut a_ab = Pattern::new("a|ab").unwrap();
+        let expected = regex::Regex::new("a|ab").unwrap();
+        assert_eq!(a_ab.replace_all("abc", "-"), "-c");
+        assert_eq!(expected.replace_all("abc", "-"), "-bc");
+        assert_eq!(a_ab.split("xaby").collect::<Vec<_>>(), ["x", "y"]);
+        assert_eq!(expected.split("xaby").collect::<Vec<_>>(), ["x", "by"]);
+        let mut leftmost_first = a_ab.find_iter("xaby").with_kind(MatchKind::LeftmostFirst);
+        assert_eq!(leftmost_first.next(), Some(1..2));
+
+        // Unchanged input is borrowed.
+        let mut dash = Pattern::new("-").unwrap();
+        assert!(matches!(dash.replace_all("abc", "+"), Cow::Borrowed("abc")));
+
+        let input = "2021-04 and 1999-12";
+        let mut year = Pattern::new("(?P<y>[0-9]{4})").unwrap();
+        assert_eq!(year.replace_all(input, NoExpand("$y")), "$y-04 and $y-12");
+        let next_year = |caps: &Captures| {
+            let year: u32 = input[caps.get("y").unwrap().bytes()].parse().unwrap();
+            (year + 1).to_string()
+        };
+        assert_eq!(year.replace_all(input, next_year), "2022-04 and 2000-12");
+        assert_eq!(
+            year.replace(input, String::from("<$y>")),
+            "<2021>-04 and 1999-12"
+        );
+    }
+}
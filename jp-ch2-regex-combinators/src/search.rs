@@ -0,0 +1,628 @@
+use super::{Captures, Pos, Regex, Symbol, Tags};
+use std::ops::Range;
+
+/// Which match to report, when several matches overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchKind {
+    /// The match that starts first, and among those, the one that a backtracking matcher would
+    /// find first: the earlier alternative of an `alt` wins, and `star`, `maybe` and repeats
+    /// match as many times as they can. This is what the `regex` crate does. Combinators that
+    /// don't implement the tagged methods can't say which way their matches went (see
+    /// `Tags::choose`), so they pick between the ways arbitrarily.
+    LeftmostFirst,
+    /// The match that starts first, and among those, the one that ends first. This is the first
+    /// match that a left-to-right scan can be sure of. (This is not leftmost-first, which picks by
+    /// the order of alternatives: for `ab|a` on "ab" it reports `0..2`, where this reports
+    /// `0..1`.)
+    LeftmostShortest,
+    /// The match that starts first, and among those, the one that ends last (as in POSIX).
+    LeftmostLongest,
+}
+
+/// Find the best match of `regex` in `input[at..]`, with positions relative to the start of
+/// `input`. If `groups` is false, only the span of the result is needed, so it can be found
+/// without running to the end of a match that is sure to last to the end of the input.
+///
+/// This runs the regex with an implicit `.*` prefix, by calling `start` at every position. Each
+/// tracked string is tagged with where it started, and since `Tags::prefer` favors earlier
+/// starts, the tags of the accepted string tell us where the leftmost match starts. Once some
+/// match has been found there is no point starting any more strings, and the search ends as soon
+/// as the strings already being tracked die. For leftmost-first, the tags also record which way
+/// each string went, so that a later match can be told apart from one a backtracking matcher
+/// would have found first.
+pub(crate) fn search<R: Regex>(
+    regex: &mut R,
+    input: &str,
+    at: usize,
+    kind: MatchKind,
+    groups: bool,
+) -> Option<Captures> {
+    search_before(regex, input, at, input.len() + 1, kind, groups)
+}
+
+/// Like `search`, but only for matches that start before `limit`.
+fn search_before<R: Regex>(
+    regex: &mut R,
+    input: &str,
+    at: usize,
+    limit: usize,
+    kind: MatchKind,
+    groups: bool,
+) -> Option<Captures> {
+    let mut pos = Pos {
+        byte: at,
+        char: input[..at].chars().count(),
+    };
+    let context = regex.uses_context();
+    let mut chars = input[at..].chars().peekable();
+    let mut best: Option<(Tags, Pos)> = None;
+    // A copy of the regex that only tracks the strings that started at this position.
+    let mut anchored: Option<(Pos, R)> = None;
+    regex.initialize();
+    if context {
+        regex.context(input[..at].chars().next_back(), chars.peek().copied());
+    }
+    loop {
+        if best.is_none() && pos.byte < limit {
+            let tags = match kind {
+                MatchKind::LeftmostFirst => Tags::ranked(pos),
+                _ => Tags::new(pos),
+            };
+            regex.start_tagged(&tags, pos);
+        }
+        if let Some(tags) = regex.accepted_tags(pos) {
+            let better = match &best {
+                None => true,
+                Some((best, _)) => match kind {
+                    MatchKind::LeftmostFirst => tags.precedes(best),
+                    MatchKind::LeftmostShortest => tags.origin() < best.origin(),
+                    MatchKind::LeftmostLongest => tags.origin() <= best.origin(),
+                },
+            };
+            if better {
+                best = Some((tags, pos));
+            }
+        }
+        // Once the regex is saturated, follow the strings that started where the best match so
+        // far did, to tell whether that match will last to the end of the input.
+        let start = best.as_ref().map(|(best, _)| best.origin());
+        if let Some(start) = start.filter(|_| !groups && kind == MatchKind::LeftmostLongest) {
+            if anchored.as_ref().map(|(from, _)| *from) != Some(start) {
+                anchored = None;
+                if regex.is_saturated() {
+                    anchored = Some((start, anchored_at(regex, input, start, pos)));
+                }
+            }
+            if let Some((_, anchored)) = &anchored {
+                if anchored.is_saturated() {
+                    return Some(saturated_end(regex, input, at, start, pos, kind));
+                }
+            }
+        }
+        match chars.next() {
+            None => break,
+            Some(ch) => {
+                pos = pos.after(ch);
+                if context {
+                    regex.context(Some(ch), chars.peek().copied());
+                }
+                regex.advance_tagged(ch, pos);
+                if let Some((_, anchored)) = &mut anchored {
+                    if context {
+                        anchored.context(Some(ch), chars.peek().copied());
+                    }
+                    anchored.advance(ch);
+                }
+            }
+        }
+        if (best.is_some() || pos.byte >= limit) && regex.is_dead() {
+            break;
+        }
+    }
+    best.map(|(tags, end)| Captures::new(&tags, end))
+}
+
+/// A copy of `regex` that has only tracked the string from `start` to `pos`.
+fn anchored_at<R: Regex>(regex: &R, input: &str, start: Pos, pos: Pos) -> R {
+    let mut anchored = regex.clone();
+    anchored.initialize();
+    let context = anchored.uses_context();
+    let mut chars = input[start.byte..].chars().peekable();
+    if context {
+        anchored.context(
+            input[..start.byte].chars().next_back(),
+            chars.peek().copied(),
+        );
+    }
+    anchored.start();
+    for ch in input[start.byte..pos.byte].chars() {
+        chars.next();
+        if context {
+            anchored.context(Some(ch), chars.peek().copied());
+        }
+        anchored.advance(ch);
+    }
+    anchored
+}
+
+/// The leftmost-longest match, when the strings that started at `start`, where the best match so
+/// far starts, are saturated at `pos`. That match lasts to the end of the input, unless one that
+/// starts earlier turns up later, which this checks by searching again for just those.
+fn saturated_end<R: Regex>(
+    regex: &R,
+    input: &str,
+    at: usize,
+    start: Pos,
+    pos: Pos,
+    kind: MatchKind,
+) -> Captures {
+    if let Some(caps) = search_before(&mut regex.clone(), input, at, start.byte, kind, false) {
+        return caps;
+    }
+    let end = Pos {
+        byte: input.len(),
+        char: pos.char + input[pos.byte..].chars().count(),
+    };
+    Captures::new(&Tags::new(start), end)
+}
+
+/// The end of the best match of `regex` that starts exactly at `input[at..]`.
+fn anchored_search<R: Regex>(
+    regex: &mut R,
+    input: &str,
+    at: usize,
+    kind: MatchKind,
+) -> Option<usize> {
+    if kind == MatchKind::LeftmostFirst {
+        // Which match is first depends on the tags, so search with them, starting only at `at`.
+        let caps = search_before(regex, input, at, at + 1, kind, false)?;
+        return Some(caps.span().end.byte);
+    }
+    let mut end = None;
+    let context = regex.uses_context();
+    let mut chars = input[at..].char_indices().peekable();
+    regex.initialize();
+    if context {
+        let next = chars.peek().map(|&(_, ch)| ch);
+        regex.context(input[..at].chars().next_back(), next);
+    }
+    regex.start();
+    if regex.accepts() {
+        end = Some(at);
+    }
+    while let Some((i, ch)) = chars.next() {
+        if end.is_some() && kind == MatchKind::LeftmostShortest {
+            break;
+        }
+        if regex.is_saturated() {
+            // It will still accept at the end.
+            return Some(input.len());
+        }
+        if context {
+            regex.context(Some(ch), chars.peek().map(|&(_, ch)| ch));
+        }
+        regex.advance(ch);
+        if regex.accepts() {
+            end = Some(at + i + ch.len_utf8());
+        } else if regex.is_dead() {
+            break;
+        }
+    }
+    end
+}
+
+/// `Regex.is_match_symbols(input)`, for regexes that use the context.
+pub(crate) fn is_match_in_context<T: Symbol, R: Regex<T>>(
+    regex: &mut R,
+    input: impl IntoIterator<Item = T>,
+) -> bool {
+    let mut chars = input.into_iter().peekable();
+    regex.initialize();
+    regex.context(None, chars.peek().copied());
+    regex.start();
+    while let Some(ch) = chars.next() {
+        if regex.is_saturated() {
+            return true;
+        }
+        regex.context(Some(ch), chars.peek().copied());
+        regex.advance(ch);
+        if regex.is_dead() {
+            return false;
+        }
+    }
+    regex.accepts()
+}
+
+/// An iterator over the non-overlapping matches of a regex in a string, from left to right. See
+/// `Regex.find_iter(&str)`.
+pub struct FindIter<'r, 't, R: Regex> {
+    regex: &'r mut R,
+    input: &'t str,
+    kind: MatchKind,
+    last_end: usize,
+    last_match: Option<usize>,
+}
+
+impl<'r, 't, R: Regex> FindIter<'r, 't, R> {
+    pub(crate) fn new(regex: &'r mut R, input: &'t str) -> FindIter<'r, 't, R> {
+        FindIter {
+            regex,
+            input,
+            kind: MatchKind::LeftmostLongest,
+            last_end: 0,
+            last_match: None,
+        }
+    }
+
+    /// Choose which of several overlapping matches to report. The default is
+    /// `MatchKind::LeftmostLongest`.
+    pub fn with_kind(mut self, kind: MatchKind) -> FindIter<'r, 't, R> {
+        self.kind = kind;
+        self
+    }
+
+    /// The next match, with its groups if `groups` is true.
+    pub(crate) fn next_captures(&mut self, groups: bool) -> Option<Captures> {
+        loop {
+            if self.last_end > self.input.len() {
+                return None;
+            }
+            let caps = search(self.regex, self.input, self.last_end, self.kind, groups)?;
+            let range = caps.span().bytes();
+            if range.is_empty() {
+                // Make progress by starting the next search one char later. And (like the `regex`
+                // crate) skip empty matches that immediately follow another match.
+                self.last_end = next_char_boundary(self.input, range.end);
+                if Some(range.end) == self.last_match {
+                    continue;
+                }
+            } else {
+                self.last_end = range.end;
+            }
+            self.last_match = Some(range.end);
+            return Some(caps);
+        }
+    }
+}
+
+impl<'r, 't, R: Regex> Iterator for FindIter<'r, 't, R> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        self.next_captures(false).map(|caps| caps.span().bytes())
+    }
+}
+
+/// An iterator over the pieces of a string between the matches of a regex. See
+/// `Regex.split(&str)`.
+pub struct Split<'r, 't, R: Regex> {
+    matches: FindIter<'r, 't, R>,
+    input: &'t str,
+    // The end of the last match, or past the end of the input once the last piece is returned.
+    last_end: usize,
+}
+
+impl<'r, 't, R: Regex> Split<'r, 't, R> {
+    pub(crate) fn new(regex: &'r mut R, input: &'t str) -> Split<'r, 't, R> {
+        Split {
+            matches: FindIter::new(regex, input),
+            input,
+            last_end: 0,
+        }
+    }
+}
+
+impl<'r, 't, R: Regex> Iterator for Split<'r, 't, R> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        if self.last_end > self.input.len() {
+            return None;
+        }
+        match self.matches.next() {
+            Some(range) => {
+                let piece = &self.input[self.last_end..range.start];
+                self.last_end = range.end;
+                Some(piece)
+            }
+            None => {
+                let piece = &self.input[self.last_end..];
+                self.last_end = self.input.len() + 1;
+                Some(piece)
+            }
+        }
+    }
+}
+
+/// Like `Split`, but producing at most `limit` pieces, the last of which is the rest of the
+/// string. See `Regex.splitn(&str, usize)`.
+pub struct SplitN<'r, 't, R: Regex> {
+    split: Split<'r, 't, R>,
+    limit: usize,
+}
+
+impl<'r, 't, R: Regex> SplitN<'r, 't, R> {
+    pub(crate) fn new(regex: &'r mut R, input: &'t str, limit: usize) -> SplitN<'r, 't, R> {
+        SplitN {
+            split: Split::new(regex, input),
+            limit,
+        }
+    }
+}
+
+impl<'r, 't, R: Regex> Iterator for SplitN<'r, 't, R> {
+    type Item = &'t str;
+
+    fn next(&mut self) -> Option<&'t str> {
+        match self.limit {
+            0 => None,
+            1 => {
+                self.limit = 0;
+                let split = &self.split;
+                split.input.get(split.last_end..)
+            }
+            _ => {
+                self.limit -= 1;
+                self.split.next()
+            }
+        }
+    }
+}
+
+/// An iterator over the matches of a regex in a string that start at each position, from left to
+/// right. Unlike `FindIter`, these matches can overlap. See `Regex.find_overlapping(&str)`.
+pub struct OverlappingIter<'r, 't, R: Regex> {
+    regex: &'r mut R,
+    input: &'t str,
+    kind: MatchKind,
+    next_start: usize,
+}
+
+impl<'r, 't, R: Regex> OverlappingIter<'r, 't, R> {
+    pub(crate) fn new(regex: &'r mut R, input: &'t str) -> OverlappingIter<'r, 't, R> {
+        OverlappingIter {
+            regex,
+            input,
+            kind: MatchKind::LeftmostLongest,
+            next_start: 0,
+        }
+    }
+
+    /// Choose which match to report when several start at the same position. The default is
+    /// `MatchKind::LeftmostLongest`.
+    pub fn with_kind(mut self, kind: MatchKind) -> OverlappingIter<'r, 't, R> {
+        self.kind = kind;
+        self
+    }
+}
+
+impl<'r, 't, R: Regex> Iterator for OverlappingIter<'r, 't, R> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        while self.next_start <= self.input.len() {
+            let start = self.next_start;
+            self.next_start = next_char_boundary(self.input, start);
+            if let Some(end) = anchored_search(self.regex, self.input, start, self.kind) {
+                return Some(start..end);
+            }
+        }
+        None
+    }
+}
+
+/// The byte offset of the char after the one at `at`. Past the end of the input, this is just
+/// `at + 1`.
+fn next_char_boundary(input: &str, at: usize) -> usize {
+    match input[at..].chars().next() {
+        Some(ch) => at + ch.len_utf8(),
+        None => at + 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MatchKind;
+    use crate::combinators::*;
+    use crate::{Pattern, Pos, Regex, Tags};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    #[test]
+    fn find() {
+        let digit = || char_range('0', '9');
+        let mut number = seq(digit(), star(digit()));
+
+        assert_eq!(number.find("port=8080 pid=17"), Some(5..9));
+        assert_eq!(number.find("no digits"), None);
+        assert_eq!(number.find("42"), Some(0..2));
+        assert_eq!(
+            number.find_iter("port=8080 pid=17").collect::<Vec<_>>(),
+            [5..9, 14..16]
+        );
+        assert_eq!(
+            number
+                .find_iter("port=8080")
+                .with_kind(MatchKind::LeftmostShortest)
+                .collect::<Vec<_>>(),
+            [5..6, 6..7, 7..8, 8..9]
+        );
+        // Shortest, whatever the order of the alternatives.
+        let mut ab_a = alt(seq(achar('a'), achar('b')), achar('a'));
+        assert_eq!(
+            ab_a.find_iter("aba")
+                .with_kind(MatchKind::LeftmostShortest)
+                .collect::<Vec<_>>(),
+            [0..1, 2..3]
+        );
+
+        // A later match that starts earlier beats an earlier match that starts later.
+        let mut abcd = alt(
+            seq(achar('a'), seq(achar('b'), seq(achar('c'), achar('d')))),
+            achar('c'),
+        );
+        assert_eq!(abcd.find("xabcd"), Some(1..5));
+        assert_eq!(abcd.find("xabce"), Some(3..4));
+
+        // Offsets are in bytes.
+        let mut omega = seq(achar('ω'), star(achar('!')));
+        assert_eq!(omega.find("αβω!!"), Some(4..8));
+    }
+
+    // Passes everything through to `regex`, counting the chars it is advanced by.
+    #[derive(Clone)]
+    struct Counting<R>(R, Rc<Cell<usize>>);
+
+    impl<R: Regex> Regex for Counting<R> {
+        fn initialize(&mut self) {
+            self.0.initialize();
+        }
+
+        fn start(&mut self) {
+            self.0.start();
+        }
+
+        fn advance(&mut self, ch: char) {
+            self.1.set(self.1.get() + 1);
+            self.0.advance(ch);
+        }
+
+        fn accepts(&self) -> bool {
+            self.0.accepts()
+        }
+
+        fn is_dead(&self) -> bool {
+            self.0.is_dead()
+        }
+
+        fn is_saturated(&self) -> bool {
+            self.0.is_saturated()
+        }
+
+        fn start_tagged(&mut self, tags: &Tags, pos: Pos) {
+            self.0.start_tagged(tags, pos);
+        }
+
+        fn advance_tagged(&mut self, ch: char, pos: Pos) {
+            self.1.set(self.1.get() + 1);
+            self.0.advance_tagged(ch, pos);
+        }
+
+        fn accepted_tags(&self, pos: Pos) -> Option<Tags> {
+            self.0.accepted_tags(pos)
+        }
+    }
+
+    #[test]
+    fn find_saturated() {
+        // A match that is sure to last to the end of the input is found without reading the rest.
+        let count = Rc::new(Cell::new(0));
+        let mut rest = Counting(seq(achar('x'), star(dot())), count.clone());
+        let line = format!("ab x{}", "y".repeat(1000));
+        assert_eq!(rest.find(&line), Some(3..line.len()));
+        assert!(count.get() < 20, "{}", count.get());
+        count.set(0);
+        assert_eq!(rest.split(&line).collect::<Vec<_>>(), ["ab ", ""]);
+        assert_eq!(rest.replace(&line, "-"), "ab -");
+        assert!(count.get() < 100, "{}", count.get());
+        // But groups are still found by reading to the end.
+        let mut group = seq(achar('x'), group("rest", star(dot())));
+        assert_eq!(group.replace(&line, "$rest"), line.replace(" x", " "));
+
+        // A match that starts earlier can still turn up after the saturated one.
+        let mut earlier = alt(
+            seq(achar('y'), star(dot())),
+            seq(achar('a'), exactly(dot(), 4)),
+        );
+        assert_eq!(earlier.find("ayzzzz"), Some(0..5));
+        assert_eq!(earlier.find("ayzz"), Some(1..4));
+    }
+
+    #[test]
+    fn leftmost_first() {
+        // The earlier alternative wins, rather than the longer one.
+        let mut a_ab = alt(achar('a'), seq(achar('a'), achar('b')));
+        assert_eq!(a_ab.find_iter("abab").collect::<Vec<_>>(), [0..2, 2..4]);
+        assert_eq!(
+            a_ab.find_iter("abab")
+                .with_kind(MatchKind::LeftmostFirst)
+                .collect::<Vec<_>>(),
+            [0..1, 2..3]
+        );
+        assert_eq!(
+            a_ab.find_overlapping("aab")
+                .with_kind(MatchKind::LeftmostFirst)
+                .collect::<Vec<_>>(),
+            [0..1, 1..2]
+        );
+
+        // Agrees with the `regex` crate, whose matches are leftmost-first.
+        let patterns = [
+            "a|ab",
+            "ab|a",
+            "(a|ab)(c|bcd)",
+            "(a|ab)(c|bcd)|abc",
+            "(a*)(a|b)*",
+            "b?(ab|b)+",
+            "[ab]{2,3}|a",
+            "(?:a|ab){2}b?",
+            "a*b|a*",
+            r"\ba|a\B|\w+",
+            "",
+        ];
+        let inputs = [
+            "", "a", "ab", "abab", "abcd", "aabab", "babb", "b ab a", "abbcd",
+        ];
+        for pattern in &patterns {
+            let mut regex = Pattern::new(pattern).unwrap();
+            let expected = regex::Regex::new(pattern).unwrap();
+            for input in &inputs {
+                assert_eq!(
+                    regex
+                        .find_iter(input)
+                        .with_kind(MatchKind::LeftmostFirst)
+                        .collect::<Vec<_>>(),
+                    expected
+                        .find_iter(input)
+                        .map(|m| m.range())
+                        .collect::<Vec<_>>(),
+                    "{} on {:?}",
+                    pattern,
+                    input
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn find_empty() {
+        // Agrees with the `regex` crate on where empty matches go.
+        let inputs = ["", "a", "aab", "baaab", "ωaω"];
+        let mut a_star = star(achar('a'));
+        let expected = regex::Regex::new("a*").unwrap();
+        for input in &inputs {
+            assert_eq!(
+                a_star.find_iter(input).collect::<Vec<_>>(),
+                expected
+                    .find_iter(input)
+                    .map(|m| m.range())
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn find_overlapping() {
+        let mut word = seq(char_range('a', 'z'), star(char_range('a', 'z')));
+        assert_eq!(
+            word.find_overlapping("ab c").collect::<Vec<_>>(),
+            [0..2, 1..2, 3..4]
+        );
+        assert_eq!(
+            word.find_overlapping("ab c")
+                .with_kind(MatchKind::LeftmostShortest)
+                .collect::<Vec<_>>(),
+            [0..1, 1..2, 3..4]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use super::Regex;
+
+/// A collection of regexes that are all run over the input in a single pass, reporting which of
+/// them match. This is faster than calling `is_match` on each one when there are many of them,
+/// since the input is only decoded once, and regexes drop out as soon as their answer is known.
+///
+/// To mix regexes of different types, box them (see `combinators::boxed`), or use `Pattern`s.
+#[derive(Clone)]
+pub struct RegexSet<R: Regex> {
+    regexes: Vec<R>,
+    anchored: bool,
+    // Which regexes use the context, so that the others can skip it.
+    context: Vec<bool>,
+}
+
+impl<R: Regex> RegexSet<R> {
+    /// A set of `regexes`, which are referred to by their index in the order given.
+    pub fn new(regexes: impl IntoIterator<Item = R>) -> RegexSet<R> {
+        let regexes: Vec<R> = regexes.into_iter().collect();
+        RegexSet {
+            context: regexes.iter().map(|regex| regex.uses_context()).collect(),
+            regexes,
+            anchored: true,
+        }
+    }
+
+    /// Choose whether a regex must match the whole input (like `Regex.is_match`), or only
+    /// somewhere inside it (like `Regex.find`). The default is anchored.
+    pub fn with_anchored(mut self, anchored: bool) -> RegexSet<R> {
+        self.anchored = anchored;
+        self
+    }
+
+    /// The number of regexes in the set.
+    pub fn len(&self) -> usize {
+        self.regexes.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+
+    /// The indices of the regexes that match `input`, in increasing order.
+    pub fn matches(&mut self, input: &str) -> Vec<usize> {
+        self.run(input, false)
+    }
+
+    /// Does any regex in the set match `input`? This stops as soon as one does.
+    pub fn is_match(&mut self, input: &str) -> bool {
+        !self.run(input, true).is_empty()
+    }
+
+    /// Run the regexes that could still match side by side, removing each one once it is known
+    /// to match (because it is saturated, or when unanchored, accepts), or when anchored, once it
+    /// is dead.
+    fn run(&mut self, input: &str, stop_at_first: bool) -> Vec<usize> {
+        let RegexSet {
+            regexes,
+            anchored,
+            context,
+        } = self;
+        let mut matched = vec![];
+        let mut live: Vec<usize> = (0..regexes.len()).collect();
+        let mut chars = input.chars().peekable();
+        let next = chars.peek().copied();
+        for &i in &live {
+            regexes[i].initialize();
+            if context[i] {
+                regexes[i].context(None, next);
+            }
+            regexes[i].start();
+        }
+        loop {
+            live.retain(|&i| {
+                let regex = &regexes[i];
+                let done = regex.is_saturated() || (!*anchored && regex.accepts());
+                if done {
+                    matched.push(i);
+                }
+                // Unanchored regexes are started again at each position, so may come back to life.
+                !done && !(*anchored && regex.is_dead())
+            });
+            if live.is_empty() || (stop_at_first && !matched.is_empty()) {
+                break;
+            }
+            let ch = match chars.next() {
+                None => break,
+                Some(ch) => ch,
+            };
+            let next = chars.peek().copied();
+            for &i in &live {
+                let regex = &mut regexes[i];
+                if context[i] {
+                    regex.context(Some(ch), next);
+                }
+                regex.advance(ch);
+                if !*anchored {
+                    regex.start();
+                }
+            }
+        }
+        if *anchored {
+            matched.extend(live.into_iter().filter(|&i| regexes[i].accepts()));
+        }
+        matched.sort_unstable();
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::Pattern;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // Counts the chars it is advanced over.
+    #[derive(Clone)]
+    struct Counting<R>(R, Rc<Cell<usize>>);
+
+    impl<R: Regex> Regex for Counting<R> {
+        fn initialize(&mut self) {
+            self.0.initialize();
+        }
+
+        fn start(&mut self) {
+            self.0.start();
+        }
+
+        fn advance(&mut self, ch: char) {
+            self.1.set(self.1.get() + 1);
+            self.0.advance(ch);
+        }
+
+        fn accepts(&self) -> bool {
+            self.0.accepts()
+        }
+
+        fn is_dead(&self) -> bool {
+            self.0.is_dead()
+        }
+
+        fn is_saturated(&self) -> bool {
+            self.0.is_saturated()
+        }
+    }
+
+    #[test]
+    fn regex_set() {
+        let patterns = [
+            r"\w+",
+            r"ERROR.*",
+            r"[0-9]+",
+            r".*timeout.*",
+            r"WARN|ERROR",
+            r"\bdisk\b",
+        ];
+        let regexes = patterns
+            .iter()
+            .map(|pattern| Pattern::new(pattern).unwrap());
+        let mut anchored = RegexSet::new(regexes.clone());
+        let mut unanchored = RegexSet::new(regexes).with_anchored(false);
+        assert_eq!(anchored.len(), 6);
+        let expected = regex::RegexSet::new(patterns).unwrap();
+        let anchored_patterns = patterns.iter().map(|pattern| format!("^(?:{})$", pattern));
+        let expected_anchored = regex::RegexSet::new(anchored_patterns).unwrap();
+        for input in &[
+            "",
+            "ERROR",
+            "ERROR: disk full",
+            "WARN timeout on diskette",
+            "2021",
+            "ok",
+            "timeout",
+        ] {
+            let indices = expected_anchored
+                .matches(input)
+                .into_iter()
+                .collect::<Vec<_>>();
+            assert_eq!(anchored.matches(input), indices, "{:?}", input);
+            assert_eq!(anchored.is_match(input), !indices.is_empty());
+            let indices = expected.matches(input).into_iter().collect::<Vec<_>>();
+            assert_eq!(unanchored.matches(input), indices, "{:?}", input);
+            assert_eq!(unanchored.is_match(input), !indices.is_empty());
+        }
+
+        // Regexes stop being run once they're dead, or once they're known to match.
+        let count = Rc::new(Cell::new(0));
+        let mut set = RegexSet::new(vec![
+            Counting(boxed(seq(achar('a'), achar('b'))), count.clone()),
+            Counting(boxed(seq(achar('x'), star(dot()))), count.clone()),
+        ]);
+        assert_eq!(set.matches("xaaaaaaaaa"), [1]);
+        assert_eq!(count.get(), 2);
+        let mut set = set.with_anchored(false);
+        count.set(0);
+        assert_eq!(set.matches("aaaab"), [0]);
+        assert_eq!(count.get(), 10);
+        assert!(RegexSet::<Pattern>::new(vec![]).matches("a").is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+use super::Regex;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// A matching session that is fed its input a piece at a time, e.g. as it arrives over the
+/// network, rather than all at once like `Regex.is_match(&str)`. It keeps only the regex's state,
+/// so it takes constant space however long the input is.
+///
+/// Input can be fed as `&str`s, or as raw bytes, in which case a UTF-8 sequence can be split
+/// across chunks.
+///
+/// If the regex uses the context (see `Regex.context`), as `word_boundary()` does, each char is
+/// only fed to it once the char after it is known.
+#[derive(Clone)]
+pub struct Matcher<R: Regex> {
+    regex: R,
+    context: bool,
+    // The last char, if the regex uses the context and hasn't been fed it yet. This is `None`
+    // before the first char, when the regex hasn't even been started.
+    held: Option<char>,
+    // The start of a UTF-8 sequence that was split across chunks.
+    partial: [u8; 4],
+    partial_len: usize,
+    // The number of bytes fed so far.
+    offset: u64,
+}
+
+impl<R: Regex> Matcher<R> {
+    /// Start a session, matching against the empty input.
+    pub fn new(mut regex: R) -> Matcher<R> {
+        let context = regex.uses_context();
+        regex.initialize();
+        if !context {
+            regex.start();
+        }
+        Matcher {
+            regex,
+            context,
+            held: None,
+            partial: [0; 4],
+            partial_len: 0,
+            offset: 0,
+        }
+    }
+
+    /// Append `input` to the input so far. This fails if the bytes fed before it ended in the
+    /// middle of a UTF-8 sequence.
+    pub fn feed(&mut self, input: &str) -> Result<(), Utf8Error> {
+        if self.partial_len > 0 {
+            return Err(self.partial_error());
+        }
+        self.advance(input);
+        self.offset += input.len() as u64;
+        Ok(())
+    }
+
+    /// Append the UTF-8 encoded `bytes` to the input so far. They may start or end in the middle
+    /// of a UTF-8 sequence, if the rest of it is in the chunk before or after. Invalid UTF-8 is
+    /// skipped (the rest of the chunk is still fed), and the first invalid sequence is reported.
+    pub fn feed_bytes(&mut self, mut bytes: &[u8]) -> Result<(), Utf8Error> {
+        let mut result = Ok(());
+        if self.partial_len > 0 {
+            // Finish off the sequence from the last chunk, by decoding it with the start of this
+            // one.
+            let mut joined = [0; 8];
+            let taken = bytes.len().min(4);
+            joined[..self.partial_len].copy_from_slice(&self.partial[..self.partial_len]);
+            joined[self.partial_len..self.partial_len + taken].copy_from_slice(&bytes[..taken]);
+            let joined = &joined[..self.partial_len + taken];
+            let len = match std::str::from_utf8(joined) {
+                Ok(input) => input.chars().next().unwrap().len_utf8(),
+                Err(err) if err.valid_up_to() > 0 => {
+                    let input = std::str::from_utf8(&joined[..err.valid_up_to()]).unwrap();
+                    input.chars().next().unwrap().len_utf8()
+                }
+                Err(err) => match err.error_len() {
+                    None => {
+                        // Still not finished.
+                        self.partial[self.partial_len..self.partial_len + taken]
+                            .copy_from_slice(&bytes[..taken]);
+                        self.partial_len += taken;
+                        return Ok(());
+                    }
+                    Some(len) => {
+                        result = Err(self.error_at(0));
+                        self.offset += len as u64;
+                        bytes = &bytes[len - self.partial_len..];
+                        self.partial_len = 0;
+                        0
+                    }
+                },
+            };
+            if len > 0 {
+                self.advance(std::str::from_utf8(&joined[..len]).unwrap());
+                self.offset += len as u64;
+                bytes = &bytes[len - self.partial_len..];
+                self.partial_len = 0;
+            }
+        }
+        loop {
+            match std::str::from_utf8(bytes) {
+                Ok(input) => {
+                    self.advance(input);
+                    self.offset += bytes.len() as u64;
+                    return result;
+                }
+                Err(err) => {
+                    let valid = err.valid_up_to();
+                    self.advance(std::str::from_utf8(&bytes[..valid]).unwrap());
+                    match err.error_len() {
+                        Some(len) => {
+                            if result.is_ok() {
+                                result = Err(self.error_at(valid));
+                            }
+                            self.offset += (valid + len) as u64;
+                            bytes = &bytes[valid + len..];
+                        }
+                        None => {
+                            // The rest is the start of a sequence that continues in the next
+                            // chunk.
+                            let rest = &bytes[valid..];
+                            self.partial[..rest.len()].copy_from_slice(rest);
+                            self.partial_len = rest.len();
+                            self.offset += valid as u64;
+                            return result;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Feed everything read from `reader`, a chunk at a time. This stops reading early if the
+    /// regex can no longer match. Invalid UTF-8 is reported as an `io::ErrorKind::InvalidData`
+    /// error. Once the regex will match however the input continues, the rest is still read, to
+    /// check that it is valid UTF-8, but the regex isn't advanced over it.
+    pub fn feed_reader(&mut self, mut reader: impl io::Read) -> io::Result<()> {
+        let mut buffer = [0; 8 * 1024];
+        while !self.is_dead() {
+            let len = match reader.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(len) => len,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            self.feed_bytes(&buffer[..len])
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        }
+        Ok(())
+    }
+
+    /// Does the regex match the input so far? This is false if the input ends in the middle of a
+    /// UTF-8 sequence.
+    pub fn is_accepting(&self) -> bool {
+        if self.partial_len > 0 {
+            return false;
+        }
+        if self.context {
+            let mut ended = self.clone();
+            ended.end_input();
+            return ended.regex.accepts();
+        }
+        self.regex.accepts()
+    }
+
+    /// Is it true that the regex won't match, however the input continues?
+    pub fn is_dead(&self) -> bool {
+        let unstarted = self.context && self.held.is_none();
+        !unstarted && self.regex.is_dead()
+    }
+
+    /// End the session, and say whether the regex matches the whole input. This fails if the
+    /// input ended in the middle of a UTF-8 sequence.
+    pub fn finish(mut self) -> Result<bool, Utf8Error> {
+        if self.partial_len > 0 {
+            return Err(self.error_at(0));
+        }
+        self.end_input();
+        Ok(self.regex.accepts())
+    }
+
+    /// Go back to matching against the empty input.
+    pub fn reset(&mut self) {
+        self.regex.initialize();
+        if !self.context {
+            self.regex.start();
+        }
+        self.held = None;
+        self.partial_len = 0;
+        self.offset = 0;
+    }
+
+    /// End the session, and get the regex back, having been fed the whole input.
+    pub fn into_inner(mut self) -> R {
+        self.end_input();
+        self.regex
+    }
+
+    fn advance(&mut self, input: &str) {
+        for ch in input.chars() {
+            if self.is_dead() || self.regex.is_saturated() {
+                return;
+            }
+            if !self.context {
+                self.regex.advance(ch);
+                continue;
+            }
+            match self.held.replace(ch) {
+                None => {
+                    self.regex.context(None, Some(ch));
+                    self.regex.start();
+                }
+                Some(prev) => {
+                    self.regex.context(Some(prev), Some(ch));
+                    self.regex.advance(prev);
+                }
+            }
+        }
+    }
+
+    /// Feed the regex the held char, now that it's known to be the last one.
+    fn end_input(&mut self) {
+        if !self.context {
+            return;
+        }
+        match self.held.take() {
+            None => {
+                self.regex.context(None, None);
+                self.regex.start();
+            }
+            Some(last) => {
+                self.regex.context(Some(last), None);
+                self.regex.advance(last);
+            }
+        }
+        self.context = false;
+    }
+
+    /// The error for a UTF-8 sequence that was started by the last chunk but never finished. The
+    /// sequence is skipped.
+    fn partial_error(&mut self) -> Utf8Error {
+        let error = self.error_at(0);
+        self.offset += self.partial_len as u64;
+        self.partial_len = 0;
+        error
+    }
+
+    fn error_at(&self, index: usize) -> Utf8Error {
+        Utf8Error {
+            offset: self.offset + index as u64,
+        }
+    }
+}
+
+/// An error from feeding a `Matcher` input that is not valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utf8Error {
+    /// The byte offset of the invalid sequence, counting from the start of the session.
+    pub offset: u64,
+}
+
+impl fmt::Display for Utf8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid UTF-8 at byte {}", self.offset)
+    }
+}
+
+impl Error for Utf8Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+
+    // A reader that hands out its input a few bytes at a time.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.0.len().min(buf.len()).min(3);
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn streaming() {
+        let greek = plus(char_range('α', 'ω'));
+        let input = "αβγδ".as_bytes();
+
+        // Split the input at every pair of byte offsets, including mid-char.
+        for i in 0..=input.len() {
+            for j in i..=input.len() {
+                let mut matcher = Matcher::new(greek.clone());
+                for chunk in &[&input[..i], &input[i..j], &input[j..]] {
+                    matcher.feed_bytes(chunk).unwrap();
+                }
+                assert!(matcher.is_accepting());
+                assert_eq!(matcher.finish(), Ok(true));
+            }
+        }
+
+        let mut matcher = Matcher::new(greek.clone());
+        matcher.feed("αβ").unwrap();
+        assert!(matcher.is_accepting());
+        matcher.feed_bytes(&"γ".as_bytes()[..1]).unwrap();
+        assert!(!matcher.is_accepting());
+        assert_eq!(matcher.feed("δ"), Err(Utf8Error { offset: 4 }));
+        matcher.feed("δ!").unwrap();
+        assert!(matcher.is_dead());
+        assert_eq!(matcher.finish(), Ok(false));
+
+        // Invalid bytes are skipped.
+        let mut matcher = Matcher::new(greek.clone());
+        assert_eq!(
+            matcher.feed_bytes(b"\xCE\xB1\xFF\xCE\xB2\xFF"),
+            Err(Utf8Error { offset: 2 })
+        );
+        assert!(matcher.is_accepting());
+        assert_eq!(matcher.feed_bytes(b"\xCE"), Ok(()));
+        assert_eq!(matcher.feed_bytes(b"\xB3\xCE"), Ok(()));
+        assert_eq!(matcher.feed_bytes(b"a"), Err(Utf8Error { offset: 8 }));
+        assert!(matcher.is_dead());
+        matcher.reset();
+        matcher.feed_bytes(b"\xCE").unwrap();
+        assert_eq!(matcher.finish(), Err(Utf8Error { offset: 0 }));
+
+        let long = "α".repeat(100_000);
+        let mut matcher = Matcher::new(greek.clone());
+        matcher.feed_reader(Trickle(long.as_bytes())).unwrap();
+        assert_eq!(matcher.finish(), Ok(true));
+
+        let mut matcher = Matcher::new(greek);
+        matcher.feed_reader(&b"\xCE\xB1\xCE"[..]).unwrap();
+        assert_eq!(matcher.finish(), Err(Utf8Error { offset: 2 }));
+        let err = Matcher::new(star(dot()))
+            .feed_reader(&b"ab\x80"[..])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "invalid UTF-8 at byte 2");
+    }
+}
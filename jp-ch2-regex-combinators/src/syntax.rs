@@ -0,0 +1,499 @@
+use super::assertions::{self, Anchor};
+use super::case;
+use super::predicates::{self, CharSet};
+use std::error::Error;
+use std::fmt;
+
+/// A description of how a regex was built, as returned by `Regex.syntax()`.
+///
+/// Its `Display` form is the regex in the syntax that `Pattern` parses, like
+/// `(0|[1-9][0-9]*)(\.[0-9]*)?`, using `(?:..)` only where it's needed, and its `Debug` form is
+/// an indented tree, with one node per line. Parts that have no syntax (e.g. a combinator defined
+/// outside this crate) are shown by name, as `<Name>` in the `Display` form.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Syntax {
+    /// Matches only the empty string.
+    Empty,
+    /// Matches any single char in the set.
+    Class(CharSet),
+    /// Matches the (case folded) string, ignoring case.
+    LiteralCi(String),
+    /// A zero-width assertion.
+    Assert(Anchor),
+    Seq(Box<Syntax>, Box<Syntax>),
+    Alt(Box<Syntax>, Box<Syntax>),
+    Star(Box<Syntax>),
+    Maybe(Box<Syntax>),
+    Repeat(Box<Syntax>, usize, Option<usize>),
+    Group(String, Box<Syntax>),
+    /// Matches the strings that both parts match. Patterns have no syntax for this, so it's shown
+    /// as `<intersect(..)>`.
+    Intersect(Box<Syntax>, Box<Syntax>),
+    /// Matches the strings that the part doesn't match, shown as `<complement(..)>`.
+    Complement(Box<Syntax>),
+    /// Matches the strings within the given number of edits of a string that the part matches,
+    /// shown as `<fuzzy{k}(..)>`.
+    Fuzzy(Box<Syntax>, usize),
+    /// A regex with no syntax, shown by name.
+    Opaque(String),
+}
+
+/// The error from an analysis that needs to know which chars a regex treats the same, like
+/// `fuzzy_match_cost`, `Regex.next_chars` or the `generate` functions, when part of the regex has
+/// no syntax (see `Regex.syntax()`), e.g. because it is a closure passed to `one_of`. Such a part
+/// could tell any two chars apart, and trying every char would take far too long, so the analysis
+/// fails rather than guess. (The `language` analyses say `Verdict::Unknown` instead.)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NoSyntaxError {
+    /// The name of the part, as in `Syntax::Opaque`.
+    pub part: String,
+}
+
+impl fmt::Display for NoSyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}> has no syntax", self.part)
+    }
+}
+
+impl Error for NoSyntaxError {}
+
+/// The name of `T`, without its module path or type parameters, for `Syntax::Opaque`.
+pub(crate) fn type_name<T: ?Sized>() -> String {
+    let name = std::any::type_name::<T>();
+    let name = &name[..name.find('<').unwrap_or(name.len())];
+    match name.rfind("::") {
+        Some(i) => name[i + 2..].to_owned(),
+        None => name.to_owned(),
+    }
+}
+
+/// Split the chars into the (inclusive) ranges between `boundaries`, in order, leaving out the
+/// surrogate code points.
+pub(crate) fn ranges_between(mut boundaries: Vec<u32>) -> Vec<(char, char)> {
+    boundaries.extend([0, 0xD800, 0xE000, char::MAX as u32 + 1]);
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    boundaries
+        .windows(2)
+        .filter(|pair| !(0xD800..0xE000).contains(&pair[0]) && pair[0] <= char::MAX as u32)
+        .map(|pair| {
+            let min = std::char::from_u32(pair[0]).unwrap();
+            let max = std::char::from_u32(pair[1] - 1).unwrap();
+            (min, max)
+        })
+        .collect()
+}
+
+/// The largest `Syntax.size()` that the parser and the backends that unroll repeats will build, so
+/// that a short pattern like `((a{1000}){1000}){1000}` can't use up all the memory.
+pub(crate) const SIZE_LIMIT: usize = 100_000;
+
+// How tightly the surrounding syntax binds, so that parts can tell whether they need a `(?:..)`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Alt,
+    Seq,
+    Repeat,
+    Atom,
+}
+
+impl Syntax {
+    fn write(&self, f: &mut fmt::Formatter, prec: Prec) -> fmt::Result {
+        let own = match self {
+            Syntax::Empty if prec == Prec::Atom => return f.write_str("(?:)"),
+            Syntax::Empty => return Ok(()),
+            Syntax::Seq(..) => Prec::Seq,
+            Syntax::Alt(..) => Prec::Alt,
+            Syntax::Star(_) | Syntax::Maybe(_) | Syntax::Repeat(..) => Prec::Repeat,
+            _ => Prec::Atom,
+        };
+        if own < prec {
+            f.write_str("(?:")?;
+            self.write(f, Prec::Alt)?;
+            return f.write_str(")");
+        }
+        match self {
+            Syntax::Empty => Ok(()),
+            Syntax::Class(class) => write_class(f, class),
+            Syntax::LiteralCi(literal) => {
+                f.write_str("(?i:")?;
+                for ch in literal.chars() {
+                    write_char(f, ch, "\\.+*?()|[]{}^$")?;
+                }
+                f.write_str(")")
+            }
+            Syntax::Assert(anchor) => write!(f, "{}", anchor),
+            Syntax::Seq(first, second) => {
+                first.write(f, Prec::Seq)?;
+                second.write(f, Prec::Seq)
+            }
+            Syntax::Alt(left, right) => {
+                left.write(f, Prec::Alt)?;
+                f.write_str("|")?;
+                right.write(f, Prec::Alt)
+            }
+            Syntax::Star(regex) => {
+                regex.write(f, Prec::Atom)?;
+                f.write_str("*")
+            }
+            Syntax::Maybe(regex) => {
+                regex.write(f, Prec::Atom)?;
+                f.write_str("?")
+            }
+            Syntax::Repeat(regex, min, max) => {
+                regex.write(f, Prec::Atom)?;
+                match (min, max) {
+                    (0, None) => f.write_str("*"),
+                    (1, None) => f.write_str("+"),
+                    (0, Some(1)) => f.write_str("?"),
+                    (min, None) => write!(f, "{{{},}}", min),
+                    (min, Some(max)) if min == max => write!(f, "{{{}}}", min),
+                    (min, Some(max)) => write!(f, "{{{},{}}}", min, max),
+                }
+            }
+            // Patterns name unnamed groups by their position.
+            Syntax::Group(name, regex) if name.bytes().all(|b| b.is_ascii_digit()) => {
+                f.write_str("(")?;
+                regex.write(f, Prec::Alt)?;
+                f.write_str(")")
+            }
+            Syntax::Group(name, regex) => {
+                write!(f, "(?P<{}>", name)?;
+                regex.write(f, Prec::Alt)?;
+                f.write_str(")")
+            }
+            Syntax::Intersect(left, right) => {
+                f.write_str("<intersect(")?;
+                left.write(f, Prec::Alt)?;
+                f.write_str(", ")?;
+                right.write(f, Prec::Alt)?;
+                f.write_str(")>")
+            }
+            Syntax::Complement(regex) => {
+                f.write_str("<complement(")?;
+                regex.write(f, Prec::Alt)?;
+                f.write_str(")>")
+            }
+            Syntax::Fuzzy(regex, max_edits) => {
+                write!(f, "<fuzzy{{{}}}(", max_edits)?;
+                regex.write(f, Prec::Alt)?;
+                f.write_str(")>")
+            }
+            Syntax::Opaque(name) => write!(f, "<{}>", name),
+        }
+    }
+
+    /// How many classes, assertions and literal chars there are once repeats are unrolled, which
+    /// is roughly how much state the regex needs (see `SIZE_LIMIT`).
+    pub(crate) fn size(&self) -> usize {
+        match self {
+            Syntax::Empty | Syntax::Class(_) | Syntax::Assert(_) | Syntax::Opaque(_) => 1,
+            Syntax::LiteralCi(literal) => literal.chars().count(),
+            Syntax::Seq(first, second)
+            | Syntax::Alt(first, second)
+            | Syntax::Intersect(first, second) => first.size().saturating_add(second.size()),
+            Syntax::Star(regex)
+            | Syntax::Maybe(regex)
+            | Syntax::Group(_, regex)
+            | Syntax::Complement(regex) => regex.size(),
+            Syntax::Fuzzy(regex, max_edits) => regex.size().saturating_mul(max_edits + 1),
+            Syntax::Repeat(regex, min, max) => {
+                regex.size().saturating_mul(max.unwrap_or(*min).max(1))
+            }
+        }
+    }
+
+    /// Add the starts and ends of ranges of chars that this syntax treats the same to
+    /// `boundaries`, so that any two chars with no boundary between them behave the same. Fails if
+    /// some parts have no syntax, and so might tell apart chars with no boundary between them (the
+    /// boundaries of the other parts are still added).
+    pub(crate) fn add_boundaries(&self, boundaries: &mut Vec<u32>) -> Result<(), NoSyntaxError> {
+        let mut add = |class: &CharSet| {
+            for &(min, max) in class.ranges() {
+                boundaries.extend([min as u32, max as u32 + 1]);
+            }
+        };
+        match self {
+            Syntax::Empty => Ok(()),
+            Syntax::Class(class) => {
+                add(class);
+                Ok(())
+            }
+            // Chars fold to a char in their simple case folding class, except for the irregular
+            // ones, each of which may fold differently from its neighbours.
+            Syntax::LiteralCi(literal) => {
+                for ch in literal.chars() {
+                    add(&predicates::set(&[(ch, ch)]).case_fold());
+                }
+                for &(min, max) in case::irregular_folds().ranges() {
+                    boundaries.extend(min as u32..=max as u32 + 1);
+                }
+                Ok(())
+            }
+            // Anchors only look at whether chars are newlines or word chars.
+            Syntax::Assert(_) => {
+                add(assertions::word_chars());
+                add(&predicates::set(&[('\n', '\n')]));
+                Ok(())
+            }
+            Syntax::Seq(first, second)
+            | Syntax::Alt(first, second)
+            | Syntax::Intersect(first, second) => {
+                let first = first.add_boundaries(boundaries);
+                let second = second.add_boundaries(boundaries);
+                first.and(second)
+            }
+            // Edits insert or substitute any char, so they don't tell any chars apart themselves.
+            Syntax::Star(regex)
+            | Syntax::Maybe(regex)
+            | Syntax::Repeat(regex, _, _)
+            | Syntax::Group(_, regex)
+            | Syntax::Complement(regex)
+            | Syntax::Fuzzy(regex, _) => regex.add_boundaries(boundaries),
+            Syntax::Opaque(name) => Err(NoSyntaxError { part: name.clone() }),
+        }
+    }
+
+    fn write_tree(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{:1$}", "", depth * 2)?;
+        let children: Vec<&Syntax> = match self {
+            Syntax::Empty => {
+                f.write_str("empty")?;
+                vec![]
+            }
+            Syntax::Class(_) => {
+                write!(f, "one_of {}", self)?;
+                vec![]
+            }
+            Syntax::LiteralCi(literal) => {
+                write!(f, "literal_ci {:?}", literal)?;
+                vec![]
+            }
+            Syntax::Assert(assertion) => {
+                write!(f, "assert {}", assertion)?;
+                vec![]
+            }
+            Syntax::Seq(first, second) => {
+                f.write_str("seq")?;
+                vec![first, second]
+            }
+            Syntax::Alt(left, right) => {
+                f.write_str("alt")?;
+                vec![left, right]
+            }
+            Syntax::Star(regex) => {
+                f.write_str("star")?;
+                vec![regex]
+            }
+            Syntax::Maybe(regex) => {
+                f.write_str("maybe")?;
+                vec![regex]
+            }
+            Syntax::Repeat(regex, min, max) => {
+                match max {
+                    Some(max) => write!(f, "repeat {{{},{}}}", min, max)?,
+                    None => write!(f, "repeat {{{},}}", min)?,
+                }
+                vec![regex]
+            }
+            Syntax::Group(name, regex) => {
+                write!(f, "group {:?}", name)?;
+                vec![regex]
+            }
+            Syntax::Intersect(left, right) => {
+                f.write_str("intersect")?;
+                vec![left, right]
+            }
+            Syntax::Complement(regex) => {
+                f.write_str("complement")?;
+                vec![regex]
+            }
+            Syntax::Fuzzy(regex, max_edits) => {
+                write!(f, "fuzzy {{{}}}", max_edits)?;
+                vec![regex]
+            }
+            Syntax::Opaque(name) => {
+                f.write_str(name)?;
+                vec![]
+            }
+        };
+        for child in children {
+            f.write_str("\n")?;
+            child.write_tree(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, Prec::Alt)
+    }
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_tree(f, 0)
+    }
+}
+
+fn write_class(f: &mut fmt::Formatter, class: &CharSet) -> fmt::Result {
+    let ranges = class.ranges();
+    if let [(min, max)] = ranges {
+        if (*min, *max) == ('\0', char::MAX) {
+            return f.write_str(".");
+        }
+        if min == max {
+            return write_char(f, *min, "\\.+*?()|[]{}^$");
+        }
+    }
+    for (letter, perl) in [
+        ('d', predicates::digit()),
+        ('w', predicates::word()),
+        ('s', predicates::space()),
+    ] {
+        if *class == perl {
+            return write!(f, "\\{}", letter);
+        }
+        if *class == perl.negate() {
+            return write!(f, "\\{}", letter.to_ascii_uppercase());
+        }
+    }
+    // Write whichever of the class and its negation is shorter (and not empty).
+    let negated = class.negate();
+    let (prefix, ranges) = if ranges.is_empty() || negated.ranges().len() < ranges.len() {
+        ("[^", negated.ranges())
+    } else {
+        ("[", ranges)
+    };
+    f.write_str(prefix)?;
+    for &(min, max) in ranges {
+        write_char(f, min, "\\[]^-")?;
+        // Only write a range for three or more chars.
+        if max as u32 > min as u32 + 1 {
+            f.write_str("-")?;
+        }
+        if min != max {
+            write_char(f, max, "\\[]^-")?;
+        }
+    }
+    f.write_str("]")
+}
+
+// Write `ch`, escaping it if it's in `special`, or if it isn't printable.
+fn write_char(f: &mut fmt::Formatter, ch: char, special: &str) -> fmt::Result {
+    match ch {
+        '\n' => f.write_str("\\n"),
+        '\r' => f.write_str("\\r"),
+        '\t' => f.write_str("\\t"),
+        _ if special.contains(ch) => write!(f, "\\{}", ch),
+        // `escape_debug` escapes unprintable chars, along with quotes.
+        _ if ch != '"' && ch != '\'' && ch.escape_debug().len() > 1 => {
+            write!(f, "\\x{{{:X}}}", ch as u32)
+        }
+        _ => write!(f, "{}", ch),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::predicates::{any, is, none_of, not, or, range, set};
+    use crate::{Pattern, Predicate, Regex};
+
+    #[test]
+    fn syntax() {
+        let number = seq(
+            group(
+                "1",
+                alt(
+                    achar('0'),
+                    seq(char_range('1', '9'), star(char_range('0', '9'))),
+                ),
+            ),
+            maybe(group("2", seq(achar('.'), star(one_of(range('0', '9')))))),
+        );
+        assert_eq!(number.syntax().to_string(), r"(0|[1-9][0-9]*)(\.[0-9]*)?");
+        assert_eq!(
+            format!("{:?}", number.syntax()),
+            [
+                "seq",
+                "  group \"1\"",
+                "    alt",
+                "      one_of 0",
+                "      seq",
+                "        one_of [1-9]",
+                "        star",
+                "          one_of [0-9]",
+                "  maybe",
+                "    group \"2\"",
+                "      seq",
+                "        one_of \\.",
+                "        star",
+                "          one_of [0-9]",
+            ]
+            .join("\n")
+        );
+
+        // Patterns print back to equivalent patterns, with `(?:..)` only where it's needed.
+        for (pattern, canonical) in [
+            (r"(0|[1-9][0-9]*)(\.[0-9]*)?", r"(0|[1-9][0-9]*)(\.[0-9]*)?"),
+            ("(?:ab)*|(?:a|b)c", "(?:ab)*|(?:a|b)c"),
+            ("(?:(?:a|b))", "a|b"),
+            ("(?:a*)?x{2}y{2,}z{2,3}w+", "(?:a*)?x{2}y{2,}z{2,3}w+"),
+            ("a(?:)*b||", "a(?:)*b||"),
+            (r"\d\W[^\n\]a-c][\-^]", r"\d\W[^\n\]a-c][\-\^]"),
+            (r"(?P<year>\d{4})(?i:Ab)", r"(?P<year>\d{4})(?i:ab)"),
+            (r"^\b\B$(?m)^$\A\z", r"^\b\B$(?m:^)(?m:$)^$"),
+            (r"\x{1F600}.\x00\x{10FFFF}", "😀.\\x{0}\\x{10FFFF}"),
+        ] {
+            let regex = Pattern::new(pattern).unwrap();
+            assert_eq!(regex.to_string(), canonical, "{}", pattern);
+            let reparsed = Pattern::new(canonical).unwrap();
+            assert_eq!(reparsed.to_string(), canonical, "{}", pattern);
+        }
+
+        // Predicates are shown as classes where possible.
+        fn class(predicate: impl Predicate) -> String {
+            one_of(predicate).syntax().to_string()
+        }
+        assert_eq!(class(or(is('a'), range('x', 'z'))), "[ax-z]");
+        assert_eq!(class(not(set(&[('a', 'z')]))), "[^a-z]");
+        assert_eq!(class(none_of(&[])), ".");
+        assert_eq!(class(not(any())), "[^\\x{0}-\\x{10FFFF}]");
+        assert_eq!(dot().syntax().to_string(), ".");
+        assert_eq!(literal_ci("Straße").syntax().to_string(), "(?i:strasse)");
+        assert_eq!(
+            seq(empty(), plus(seq(word_boundary(), line_start())))
+                .syntax()
+                .to_string(),
+            r"(?:\b(?m:^))+"
+        );
+
+        // Regexes without syntax fall back to a name.
+        let closure = seq(one_of(|ch: char| ch.is_ascii()), lookahead(dot()));
+        assert_eq!(closure.syntax().to_string(), "<{{closure}}><lookahead>");
+        let boxed = boxed(one_of(|ch: char| ch == 'a'));
+        assert_eq!(boxed.syntax(), Syntax::Opaque("{{closure}}".to_owned()));
+        assert_eq!(format!("{:?}", boxed), "{{closure}}");
+
+        // Boolean combinators and `fuzzy` have no pattern syntax, but still show their parts.
+        let boolean = intersect(complement(achar('a')), fuzzy(literal_ci("ab"), 1).unwrap());
+        assert_eq!(
+            boolean.syntax().to_string(),
+            "<intersect(<complement(a)>, <fuzzy{1}((?i:ab))>)>"
+        );
+        assert_eq!(
+            format!("{:?}", boolean.syntax()),
+            [
+                "intersect",
+                "  complement",
+                "    one_of a",
+                "  fuzzy {1}",
+                "    literal_ci \"ab\"",
+            ]
+            .join("\n")
+        );
+    }
+}
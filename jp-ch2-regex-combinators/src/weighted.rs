@@ -0,0 +1,355 @@
+//! Weighted matching: instead of just whether a regex matches, compute a value from all the ways
+//! it can match (its parses), like how many there are, or the cost of the cheapest one.
+//!
+//! The value is an element of a `Semiring`. Each char that a leaf of the regex (a char class or
+//! case-insensitive literal) matches has a weight, a parse weighs the product (`times`) of the
+//! weights of its chars, and the result is the sum (`plus`) of the weights of all the parses. With
+//! `bool`, this is whether the regex matches; with `Count`, how many parses there are (see
+//! `ambiguity`); and with `MinCost`, the cost of the cheapest parse.
+//!
+//! This works like the `Regex` trait's state, except that each NFA state carries a weight rather
+//! than being in the set or not. It's built from the regex's `Regex.syntax()`, so it can't weigh
+//! regexes with parts that have no syntax, like `lookahead` or combinators defined outside this
+//! crate, with `intersect`, `complement` or `fuzzy` parts, or that are too large once their
+//! repeats are unrolled.
+//!
+//! The weights aren't threaded through the `Regex` trait itself, because a weight is a type
+//! parameter of the methods that would carry it, and generic methods would stop `DynRegex` (and
+//! so `BoxedRegex` and `Pattern`) from forwarding them. Combinators also merge the strings that
+//! reach the same state, which is what keeps matching `O(NM)` but loses the count of parses, and
+//! `intersect` and `complement` don't have a meaningful weight to give. So instead, this
+//! interprets the syntax tree, whose nodes mean the same as the combinators they describe. The
+//! differential tests check that weighing with `bool` agrees with `Regex.is_match(&str)`.
+//!
+//! A parse picks a branch of each `alt` (or `maybe`) and splits the string between the parts of
+//! each `seq` and the iterations of each `star`. Iterations that match the empty string aren't
+//! counted as separate parses, since there would be infinitely many. Bounded repeats like `x{1,3}`
+//! are parsed as `x(?:x(?:x)?)?`, so they don't add any ambiguity of their own.
+
+use super::assertions::Anchor;
+use super::predicates::CharSet;
+use super::syntax::SIZE_LIMIT;
+use super::{case, Predicate, Regex, Syntax};
+
+/// Values that weights can be combined in. `plus` combines alternative parses, and `times` the
+/// parts of one parse. Both must be associative, `plus` must be commutative, `zero` and `one` are
+/// their identities, `times` distributes over `plus`, and `zero` times anything is `zero`.
+pub trait Semiring: Clone {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn plus(&self, other: &Self) -> Self;
+    fn times(&self, other: &Self) -> Self;
+}
+
+/// Whether there's a parse.
+impl Semiring for bool {
+    fn zero() -> bool {
+        false
+    }
+    fn one() -> bool {
+        true
+    }
+    fn plus(&self, other: &bool) -> bool {
+        *self || *other
+    }
+    fn times(&self, other: &bool) -> bool {
+        *self && *other
+    }
+}
+
+/// The number of parses (weighing each char 1), saturating at `u64::MAX`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Count(pub u64);
+
+impl Semiring for Count {
+    fn zero() -> Count {
+        Count(0)
+    }
+    fn one() -> Count {
+        Count(1)
+    }
+    fn plus(&self, other: &Count) -> Count {
+        Count(self.0.saturating_add(other.0))
+    }
+    fn times(&self, other: &Count) -> Count {
+        Count(self.0.saturating_mul(other.0))
+    }
+}
+
+/// The cost of the cheapest parse, where a parse costs the sum of the costs of its chars. There
+/// being no parse is represented by `u64::MAX`, which stands for infinity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MinCost(pub u64);
+
+impl Semiring for MinCost {
+    fn zero() -> MinCost {
+        MinCost(u64::MAX)
+    }
+    fn one() -> MinCost {
+        MinCost(0)
+    }
+    fn plus(&self, other: &MinCost) -> MinCost {
+        MinCost(self.0.min(other.0))
+    }
+    fn times(&self, other: &MinCost) -> MinCost {
+        MinCost(self.0.saturating_add(other.0))
+    }
+}
+
+/// Weigh the parses of all of `input` by `regex` (see the module docs), where `weight(leaf, ch)`
+/// is the weight of the leaf `leaf` of `regex.syntax()` matching `ch`. Returns `None` if `regex`
+/// has parts without syntax or boolean or fuzzy parts, or is too large once its repeats are
+/// unrolled.
+pub fn weigh<S: Semiring>(
+    regex: &impl Regex,
+    input: &str,
+    weight: impl Fn(&Syntax, char) -> S,
+) -> Option<S> {
+    let mut node = Node::build(&regex.syntax())?;
+    // The weight of the parses that start here, which is only the start of the input.
+    let mut start = S::one();
+    let mut prev = None;
+    let mut folded = vec![];
+    for ch in input.chars() {
+        let mut step = Step {
+            ch,
+            context: (prev, Some(ch)),
+            weight: &weight,
+            folded: &mut folded,
+        };
+        node.shift(&start, &mut step);
+        start = S::zero();
+        prev = Some(ch);
+    }
+    let context = (prev, None);
+    Some(start.times(&node.empty(context)).plus(&node.last(context)))
+}
+
+/// How many ways `regex` can match all of `input` (see the module docs): 0 if it doesn't match,
+/// 1 if the match is unambiguous, and more if it's ambiguous. Returns `None` if `regex` can't be
+/// weighed (see `weigh`).
+pub fn ambiguity(regex: &impl Regex, input: &str) -> Option<u64> {
+    weigh(regex, input, |_, _| Count(1)).map(|Count(count)| count)
+}
+
+type Context = (Option<char>, Option<char>);
+
+/// A `Syntax` tree in which each leaf carries the weight of the parses that end just after it.
+enum Node<S> {
+    Empty,
+    Class {
+        leaf: Syntax,
+        class: CharSet,
+        weight: S,
+    },
+    // `weights[i]` is the weight of the parses ending after `target[i]`.
+    Literal {
+        leaf: Syntax,
+        target: Vec<char>,
+        weights: Vec<S>,
+    },
+    Assert(Anchor),
+    Seq(Box<Node<S>>, Box<Node<S>>),
+    Alt(Box<Node<S>>, Box<Node<S>>),
+    Star(Box<Node<S>>),
+}
+
+/// What the leaves need to know to advance over a char.
+struct Step<'a, F> {
+    ch: char,
+    // The context of the position before `ch`.
+    context: Context,
+    weight: &'a F,
+    folded: &'a mut Vec<char>,
+}
+
+impl<S: Semiring> Node<S> {
+    fn build(syntax: &Syntax) -> Option<Node<S>> {
+        Some(match syntax {
+            Syntax::Empty => Node::Empty,
+            Syntax::Class(class) => Node::Class {
+                leaf: syntax.clone(),
+                class: class.clone(),
+                weight: S::zero(),
+            },
+            Syntax::LiteralCi(literal) => Node::Literal {
+                leaf: syntax.clone(),
+                target: literal.chars().collect(),
+                weights: vec![S::zero(); literal.chars().count()],
+            },
+            Syntax::Assert(anchor) => Node::Assert(*anchor),
+            Syntax::Seq(first, second) => Node::Seq(Node::boxed(first)?, Node::boxed(second)?),
+            Syntax::Alt(left, right) => Node::Alt(Node::boxed(left)?, Node::boxed(right)?),
+            Syntax::Star(regex) => Node::Star(Node::boxed(regex)?),
+            Syntax::Maybe(regex) => Node::Alt(Box::new(Node::Empty), Node::boxed(regex)?),
+            Syntax::Repeat(..) if syntax.size() > SIZE_LIMIT => return None,
+            Syntax::Repeat(regex, min, max) => {
+                // `x{2,4}` is `xx(?:x(?:x)?)?`, and `x{2,}` is `xxx*`.
+                let mut node = match max {
+                    None => Node::Star(Node::boxed(regex)?),
+                    Some(max) => {
+                        let mut node = Node::Empty;
+                        for _ in *min..*max {
+                            let first = Node::boxed(regex)?;
+                            let optional = Node::Seq(first, Box::new(node));
+                            node = Node::Alt(Box::new(Node::Empty), Box::new(optional));
+                        }
+                        node
+                    }
+                };
+                for _ in 0..*min {
+                    node = Node::Seq(Node::boxed(regex)?, Box::new(node));
+                }
+                node
+            }
+            Syntax::Group(_, regex) => Node::build(regex)?,
+            // Boolean combinators and edits have no meaningful weight to give (see above).
+            Syntax::Intersect(..)
+            | Syntax::Complement(_)
+            | Syntax::Fuzzy(..)
+            | Syntax::Opaque(_) => return None,
+        })
+    }
+
+    fn boxed(syntax: &Syntax) -> Option<Box<Node<S>>> {
+        Some(Box::new(Node::build(syntax)?))
+    }
+
+    /// The weight of the parses of the empty string at a position with the given context.
+    fn empty(&self, context: Context) -> S {
+        match self {
+            Node::Empty | Node::Star(_) => S::one(),
+            Node::Class { .. } => S::zero(),
+            Node::Literal { target, .. } if target.is_empty() => S::one(),
+            Node::Literal { .. } => S::zero(),
+            Node::Assert(anchor) if anchor.holds(context.0, context.1) => S::one(),
+            Node::Assert(_) => S::zero(),
+            Node::Seq(first, second) => first.empty(context).times(&second.empty(context)),
+            Node::Alt(left, right) => left.empty(context).plus(&right.empty(context)),
+        }
+    }
+
+    /// The weight of the parses that end here, after at least one char, at a position with the
+    /// given context.
+    fn last(&self, context: Context) -> S {
+        match self {
+            Node::Empty | Node::Assert(_) => S::zero(),
+            Node::Class { weight, .. } => weight.clone(),
+            Node::Literal { weights, .. } => weights.last().cloned().unwrap_or_else(S::zero),
+            Node::Seq(first, second) => {
+                let through = first.last(context).times(&second.empty(context));
+                through.plus(&second.last(context))
+            }
+            Node::Alt(left, right) => left.last(context).plus(&right.last(context)),
+            Node::Star(regex) => regex.last(context),
+        }
+    }
+
+    /// Advance over `step.ch`, where `start` is the weight of the parses that start just before
+    /// it.
+    fn shift<F: Fn(&Syntax, char) -> S>(&mut self, start: &S, step: &mut Step<F>) {
+        match self {
+            Node::Empty | Node::Assert(_) => (),
+            Node::Class {
+                leaf,
+                class,
+                weight,
+            } => {
+                *weight = if class.matches(step.ch) {
+                    start.times(&(step.weight)(leaf, step.ch))
+                } else {
+                    S::zero()
+                };
+            }
+            Node::Literal {
+                leaf,
+                target,
+                weights,
+            } => {
+                step.folded.clear();
+                case::fold(step.ch, step.folded);
+                let len = step.folded.len();
+                let mut shifted = vec![S::zero(); target.len()];
+                for i in 0..target.len() {
+                    if target[i..].starts_with(step.folded) {
+                        let before = if i == 0 { start } else { &weights[i - 1] };
+                        let weight = before.times(&(step.weight)(leaf, step.ch));
+                        shifted[i + len - 1] = shifted[i + len - 1].plus(&weight);
+                    }
+                }
+                *weights = shifted;
+            }
+            Node::Seq(first, second) => {
+                let context = step.context;
+                let through = start.times(&first.empty(context));
+                let into_second = through.plus(&first.last(context));
+                first.shift(start, step);
+                second.shift(&into_second, step);
+            }
+            Node::Alt(left, right) => {
+                left.shift(start, step);
+                right.shift(start, step);
+            }
+            Node::Star(regex) => {
+                let again = start.plus(&regex.last(step.context));
+                regex.shift(&again, step);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combinators::*;
+    use crate::{predicates, Pattern};
+
+    #[test]
+    fn weighted() {
+        let count = |pattern: &str, input: &str| {
+            let regex = Pattern::new(pattern).unwrap();
+            ambiguity(&regex, input).unwrap()
+        };
+        assert_eq!(count("ab|cd", "ab"), 1);
+        assert_eq!(count("ab|cd", "abcd"), 0);
+        assert_eq!(count("a|a", "a"), 2);
+        assert_eq!(count("a*a*", "aa"), 3);
+        assert_eq!(count("(?:a|ab)(?:c|bc)", "abc"), 2);
+        assert_eq!(count("(?:a*)*", "aa"), 2);
+        assert_eq!(count("(?:a*)*", ""), 1);
+        assert_eq!(count("a?a?", "a"), 2);
+        assert_eq!(count("a{0,2}", "a"), 1);
+        assert_eq!(count("(?:a|aa){2,}", "aaaa"), 5);
+        assert_eq!(count("(?i)ß|ss|s{2}", "SS"), 3);
+        assert_eq!(count(r"(?:\b|a)(?:\b|a)", "a"), 2);
+        assert_eq!(count(r"(?:a|b|ab|\s)*", "ab ab"), 4);
+        assert_eq!(count(r"(?m)a$\n^b|a\nb", "a\nb"), 2);
+        assert_eq!(count(r"a\Bb|a$", "ab"), 1);
+        assert_eq!(ambiguity(&seq(dot(), lookahead(dot())), "a"), None);
+        assert_eq!(ambiguity(&complement(achar('b')), "a"), None);
+        assert_eq!(ambiguity(&exactly(exactly(dot(), 1000), 1000), "a"), None);
+        assert_eq!(count(r"\bab\B|(?m)^a$", "ab"), 0);
+        assert_eq!(count(r"(?m)^a$\n^b", "a\nb"), 1);
+
+        // With `bool`, it's whether the regex matches.
+        for pattern in [r"(?:a|ab)(?:c|bc)", r"\ba+\b|x?", r"(?i:s)+"] {
+            let mut regex = Pattern::new(pattern).unwrap();
+            for input in ["", "a", "abc", "aa", "x", "sSſ", "bc"] {
+                let matches = weigh(&regex, input, |_, _| true).unwrap();
+                assert_eq!(matches, regex.is_match(input), "{} on {:?}", pattern, input);
+            }
+        }
+
+        // With `MinCost`, the cost of the cheapest parse: here, the fewest chars matched by `.`.
+        let cost = |input: &str| {
+            let regex = Pattern::new(r"(?:\w+|.)*@(?:\w+|\.)+").unwrap();
+            let dot = Syntax::Class(predicates::none_of(&[]));
+            let weight = |leaf: &Syntax, _| MinCost((*leaf == dot) as u64);
+            weigh(&regex, input, weight).unwrap()
+        };
+        assert_eq!(cost("jo@example.com"), MinCost(0));
+        assert_eq!(cost("jo.e+x@example.com"), MinCost(2));
+        assert_eq!(cost("jo@@example.com"), MinCost(1));
+        assert_eq!(cost("jo"), MinCost::zero());
+    }
+}